@@ -0,0 +1,40 @@
+#!/usr/bin/env sh
+# 编译 grammars/ 目录中查询规则对应的 Tree-sitter 语法, 输出至指定目录
+# 用法: grammars/build.sh <output-dir>
+# 缺少生成的 src/parser.c 的语法需要 tree-sitter CLI
+set -eu
+
+out=$(mkdir -p "$1" && cd "$1" && pwd)
+here=$(cd "$(dirname "$0")" && pwd)
+work=$(mktemp -d)
+trap 'rm -rf "$work"' EXIT
+
+case "$(uname -s)" in
+    Darwin) ext=dylib; shared=-dynamiclib ;;
+    *) ext=so; shared=-shared ;;
+esac
+
+while read -r name repo; do
+    git clone --quiet --depth 1 "https://github.com/$repo" "$work/$name"
+    (
+        cd "$work/$name"
+        [ -f src/parser.c ] || tree-sitter generate
+        set -- src/parser.c
+        [ -f src/scanner.c ] && set -- "$@" src/scanner.c
+        cc $shared -fPIC -O2 -Isrc "$@" -o "$out/$name.$ext"
+    )
+    cp "$here/$name.scm" "$out/"
+    echo "built $name"
+done <<LIST
+latex latex-lsp/tree-sitter-latex
+gitcommit gbprod/tree-sitter-gitcommit
+git_rebase the-mikedavis/tree-sitter-git-rebase
+ini justinmk/tree-sitter-ini
+dockerfile camdencheek/tree-sitter-dockerfile
+make alemuller/tree-sitter-make
+zig tree-sitter-grammars/tree-sitter-zig
+scala tree-sitter/tree-sitter-scala
+dart UserNobody14/tree-sitter-dart
+haskell tree-sitter/tree-sitter-haskell
+nix nix-community/tree-sitter-nix
+LIST
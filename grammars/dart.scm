@@ -0,0 +1,7 @@
+; Dart 注释匹配规则, 适用于 https://github.com/UserNobody14/tree-sitter-dart
+; 非内置语言, 仅用于 --grammar-dir 运行时加载
+; 包含 // /* */ 注释 与 /// 文档注释
+[
+  (comment)
+  (documentation_comment)
+] @comment
@@ -0,0 +1,4 @@
+; rebase 待办文件匹配规则, 适用于 https://github.com/the-mikedavis/tree-sitter-git-rebase
+; 非内置语言, 仅用于 --grammar-dir 运行时加载
+; 提交标题为正文, 命令、提交哈希 与 以 # 开头的注释行为代码
+(message) @prose
@@ -0,0 +1,17 @@
+; Git 提交信息匹配规则, 适用于 https://github.com/gbprod/tree-sitter-gitcommit
+; 非内置语言, 仅用于 --grammar-dir 运行时加载
+; 标题 与 正文为正文
+[
+  (subject)
+  (message)
+  (breaking_change)
+] @prose
+; 约定式提交前缀、尾注、以 # 开头的注释行 与 verbose 模式下的 diff 为代码
+[
+  (prefix)
+  (trailer)
+  (comment)
+  (generated_comment)
+  (diff)
+] @code
+(breaking_change (token) @code)
@@ -0,0 +1,7 @@
+; Haskell 注释匹配规则, 适用于 https://github.com/tree-sitter/tree-sitter-haskell
+; 非内置语言, 仅用于 --grammar-dir 运行时加载
+; 包含 -- {- -} 注释 与 -- | Haddock 文档注释
+[
+  (comment)
+  (haddock)
+] @comment
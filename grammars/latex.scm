@@ -0,0 +1,37 @@
+; LaTeX 匹配规则, 适用于 https://github.com/latex-lsp/tree-sitter-latex
+; 非内置语言, 仅用于 --grammar-dir 运行时加载
+; 注释匹配规则
+[
+  (line_comment)
+  (block_comment)
+  (comment_environment)
+] @comment
+; 正文匹配规则
+; 仅章节 与 普通环境中的文本为正文, 公式、命令名 与 标签引用等默认为代码
+([
+  (source_file)
+  (part)
+  (chapter)
+  (section)
+  (subsection)
+  (subsubsection)
+  (paragraph)
+  (subparagraph)
+  (enum_item)
+  (generic_environment)
+] (text) @prose)
+; 章节标题
+([
+  (part)
+  (chapter)
+  (section)
+  (subsection)
+  (subsubsection)
+  (paragraph)
+  (subparagraph)
+] (curly_group (text) @prose))
+; 排版 与 说明类命令的参数
+(generic_command
+  command: (command_name) @_name
+  arg: (curly_group (text) @prose)
+  (#any-of? @_name "\\emph" "\\textbf" "\\textit" "\\underline" "\\footnote" "\\caption" "\\title" "\\author"))
@@ -0,0 +1,3 @@
+; Makefile 注释匹配规则, 适用于 https://github.com/alemuller/tree-sitter-make
+; 非内置语言, 仅用于 --grammar-dir 运行时加载
+(comment) @comment
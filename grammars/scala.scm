@@ -0,0 +1,7 @@
+; Scala 注释匹配规则, 适用于 https://github.com/tree-sitter/tree-sitter-scala
+; 非内置语言, 仅用于 --grammar-dir 运行时加载
+; 包含 // 单行注释 与 /* */ /** */ 块注释
+[
+  (comment)
+  (block_comment)
+] @comment
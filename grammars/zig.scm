@@ -0,0 +1,4 @@
+; Zig 注释匹配规则, 适用于 https://github.com/tree-sitter-grammars/tree-sitter-zig
+; 非内置语言, 仅用于 --grammar-dir 运行时加载
+; 包含 // 普通注释 与 /// //! 文档注释
+(comment) @comment
@@ -0,0 +1,166 @@
+//! 服务端运行配置
+//!
+//! 配置由命令行参数解析, 未提供的参数使用默认值:
+//! ```bash
+//! LazyInputSwitcher [--max-frame-size <bytes>] [--read-timeout <secs>] [--write-timeout <secs>] [--auth-timeout <secs>]
+//!                   [--token-file <path>] [--daemon] [--discovery-file <path>] [--idle-timeout <secs|never>]
+//!                   [--log-level <filter>] [--log-file <path>] [--log-stderr]
+//!                   [--trace <path>] [--trace-redact] [--mock-switcher] [--mock-switch-delay <ms>]
+//!                   [--grammar-dir <path>]
+//! ```
+
+use crate::logger::LogFilter;
+use crate::switch::MockGate;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// 默认单个消息帧的最大字节数 (16 MiB)
+const DEFAULT_MAX_FRAME_SIZE: usize = 16 * 1024 * 1024;
+/// 默认读取单个消息帧的超时时间（秒）
+const DEFAULT_READ_TIMEOUT_SECS: u64 = 10;
+/// 默认向客户端写入单个消息帧的超时时间（秒）
+const DEFAULT_WRITE_TIMEOUT_SECS: u64 = 10;
+/// 默认新连接发送第一条消息 (认证) 的超时时间（秒）
+const DEFAULT_AUTH_TIMEOUT_SECS: u64 = 10;
+/// 默认所有客户端断开后 无新连接时自动退出的时间（秒）
+const DEFAULT_IDLE_TIMEOUT_SECS: u64 = 300;
+
+#[derive(Debug, Clone)]
+pub(crate) struct Config {
+    /// 单个消息帧消息内容的最大字节数, 超出时拒绝该消息并断开连接
+    pub(crate) max_frame_size: usize,
+    /// 消息帧开始到达后, 剩余部分需在此时间内读取完毕
+    pub(crate) read_timeout: Duration,
+    /// 向客户端写入消息帧的超时时间, 客户端不再读取时避免阻塞工作线程
+    pub(crate) write_timeout: Duration,
+    /// 新连接需在此时间内发送携带令牌的第一条消息, 超时后断开连接
+    pub(crate) auth_timeout: Duration,
+    /// 从文件读取认证令牌, 未提供时启动时随机生成
+    pub(crate) token_file: Option<PathBuf>,
+    /// 守护进程模式, 多个编辑器共享同一服务端
+    pub(crate) daemon: bool,
+    /// 守护进程发现文件路径, 未提供时使用运行时目录下的默认位置
+    pub(crate) discovery_file: Option<PathBuf>,
+    /// 所有客户端断开后 无新连接时自动退出的时间, None 表示永不退出
+    pub(crate) idle_timeout: Option<Duration>,
+    /// 日志级别, 如 `info,rpc=debug`
+    pub(crate) log_filter: LogFilter,
+    /// 日志文件路径, 未提供时使用状态目录下的默认位置
+    pub(crate) log_file: Option<PathBuf>,
+    /// 日志输出至 stderr 而不是文件
+    pub(crate) log_stderr: bool,
+    /// 记录请求 与 响应的 trace 文件, 未提供时不记录
+    pub(crate) trace_file: Option<PathBuf>,
+    /// trace 中不记录代码原文
+    pub(crate) trace_redact: bool,
+    /// 使用模拟输入法后端, 不操作系统输入法
+    pub(crate) mock_switcher: bool,
+    /// 模拟输入法后端每次切换的耗时, 用于复现慢速后端
+    pub(crate) mock_switch_delay: Duration,
+    /// 模拟输入法后端的切换闸门, 仅由测试设置
+    pub(crate) mock_gate: Option<MockGate>,
+    /// 运行时加载的 Tree-sitter 语法目录, 未提供时仅使用内置语法
+    pub(crate) grammar_dir: Option<PathBuf>,
+}
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            max_frame_size: DEFAULT_MAX_FRAME_SIZE,
+            read_timeout: Duration::from_secs(DEFAULT_READ_TIMEOUT_SECS),
+            write_timeout: Duration::from_secs(DEFAULT_WRITE_TIMEOUT_SECS),
+            auth_timeout: Duration::from_secs(DEFAULT_AUTH_TIMEOUT_SECS),
+            token_file: None,
+            daemon: false,
+            discovery_file: None,
+            idle_timeout: Some(Duration::from_secs(DEFAULT_IDLE_TIMEOUT_SECS)),
+            log_filter: LogFilter::default(),
+            log_file: None,
+            log_stderr: false,
+            trace_file: None,
+            trace_redact: false,
+            mock_switcher: false,
+            mock_switch_delay: Duration::ZERO,
+            mock_gate: None,
+            grammar_dir: None,
+        }
+    }
+}
+impl Config {
+    /// 解析命令行参数（不包含程序名）
+    pub(crate) fn from_args(args: impl IntoIterator<Item = String>) -> Result<Config, String> {
+        let mut config = Config::default();
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--max-frame-size" => {
+                    config.max_frame_size = Config::_value(&arg, args.next())?;
+                },
+                "--read-timeout" => {
+                    let secs: u64 = Config::_value(&arg, args.next())?;
+                    if secs == 0 {
+                        return Err(format!("{arg} must be greater than 0"));
+                    };
+                    config.read_timeout = Duration::from_secs(secs);
+                },
+                "--write-timeout" => {
+                    let secs: u64 = Config::_value(&arg, args.next())?;
+                    if secs == 0 {
+                        return Err(format!("{arg} must be greater than 0"));
+                    };
+                    config.write_timeout = Duration::from_secs(secs);
+                },
+                "--auth-timeout" => {
+                    let secs: u64 = Config::_value(&arg, args.next())?;
+                    if secs == 0 {
+                        return Err(format!("{arg} must be greater than 0"));
+                    };
+                    config.auth_timeout = Duration::from_secs(secs);
+                },
+                "--token-file" => {
+                    config.token_file = Some(Config::_value(&arg, args.next())?);
+                },
+                "--daemon" => config.daemon = true,
+                "--discovery-file" => {
+                    config.discovery_file = Some(Config::_value(&arg, args.next())?);
+                },
+                "--idle-timeout" => {
+                    let value: String = Config::_value(&arg, args.next())?;
+                    config.idle_timeout = match value.as_str() {
+                        "never" => None,
+                        secs => match Config::_value(&arg, Some(secs.to_string()))? {
+                            0 => return Err(format!("{arg} must be greater than 0 or \"never\"")),
+                            secs => Some(Duration::from_secs(secs)),
+                        },
+                    };
+                },
+                "--log-level" => {
+                    let spec: String = Config::_value(&arg, args.next())?;
+                    config.log_filter = spec.parse()?;
+                },
+                "--log-file" => {
+                    config.log_file = Some(Config::_value(&arg, args.next())?);
+                },
+                "--log-stderr" => config.log_stderr = true,
+                "--trace" => {
+                    config.trace_file = Some(Config::_value(&arg, args.next())?);
+                },
+                "--trace-redact" => config.trace_redact = true,
+                "--mock-switcher" => config.mock_switcher = true,
+                "--mock-switch-delay" => {
+                    config.mock_switch_delay = Duration::from_millis(Config::_value(&arg, args.next())?);
+                },
+                "--grammar-dir" => {
+                    config.grammar_dir = Some(Config::_value(&arg, args.next())?);
+                },
+                _ => return Err(format!("Unknown argument: {arg}")),
+            }
+        }
+        Ok(config)
+    }
+
+    fn _value<T: FromStr>(name: &str, value: Option<String>) -> Result<T, String> {
+        let value = value.ok_or(format!("Missing value for {name}"))?;
+        value.parse().map_err(|_| format!("Invalid value for {name}: {value}"))
+    }
+}
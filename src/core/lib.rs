@@ -57,6 +57,27 @@ pub enum SupportLanguage {
     Sql,
     Php,
     CSharp,
+    Ruby,
+    Swift,
+    Elixir,
+    Tsx,
+    Jsx,
+    Html,
+    Css,
+    /// Vue 单文件组件, 使用 HTML 语法解析
+    Vue,
+    /// Svelte 组件, 使用 HTML 语法解析
+    Svelte,
+    Yaml,
+    Toml,
+    /// JSON 与 JSONC, 语法本身支持注释
+    Json,
+    Markdown,
+    /// Markdown 段落内的行内语法, 仅作为 Markdown 的注入语言使用
+    MarkdownInline,
+    /// 运行时从语法目录加载的语言, 名称来自动态库文件名
+    #[serde(skip)]
+    Dynamic(&'static str),
 }
 impl SupportLanguage {
     /// SupportLanguage为可 哈希的，提高哈希表可读性
@@ -68,10 +89,10 @@ impl SupportLanguage {
     /// ```rust
     /// use crate::core::InputMethodMode;
     ///
-    /// assert_eq!(SupportLanguage::from_string("Rust".to_string()), Some(SupportLanguage::Rust));
-    /// assert!(SupportLanguage::from_string("python".to_string()).is_none());
+    /// assert_eq!(SupportLanguage::from_string("Rust"), Some(SupportLanguage::Rust));
+    /// assert!(SupportLanguage::from_string("python").is_none());
     /// ```
-    pub fn from_string(s: &String) -> Option<SupportLanguage> {
+    pub fn from_string(s: &str) -> Option<SupportLanguage> {
         match s.to_lowercase().as_str() {
             "rust" => Some(SupportLanguage::Rust),
             "python" => Some(SupportLanguage::Python),
@@ -87,18 +108,38 @@ impl SupportLanguage {
             "sql" => Some(SupportLanguage::Sql),
             "php" => Some(SupportLanguage::Php),
             "csharp" => Some(SupportLanguage::CSharp),
+            "ruby" => Some(SupportLanguage::Ruby),
+            "swift" => Some(SupportLanguage::Swift),
+            "elixir" => Some(SupportLanguage::Elixir),
+            "tsx" => Some(SupportLanguage::Tsx),
+            "jsx" => Some(SupportLanguage::Jsx),
+            "html" => Some(SupportLanguage::Html),
+            "css" => Some(SupportLanguage::Css),
+            "vue" => Some(SupportLanguage::Vue),
+            "svelte" => Some(SupportLanguage::Svelte),
+            "yaml" => Some(SupportLanguage::Yaml),
+            "toml" => Some(SupportLanguage::Toml),
+            "json" | "jsonc" => Some(SupportLanguage::Json),
+            "markdown" => Some(SupportLanguage::Markdown),
+            "markdown_inline" | "markdowninline" => Some(SupportLanguage::MarkdownInline),
             _ => None,
         }
     }
-
-    /// 将枚举转换为对应的小写字符串。
-    pub fn to_string(&self) -> String {
-        serde_json::to_string(&self).unwrap().trim_matches('"').to_lowercase()
+}
+impl Display for SupportLanguage {
+    /// 格式化输出为对应的小写字符串
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        if let SupportLanguage::Dynamic(name) = self {
+            return write!(f, "{name}");
+        };
+        let name = serde_json::to_string(&self).unwrap();
+        write!(f, "{}", name.trim_matches('"').to_lowercase())
     }
 }
 
 /// 表示文本编辑器中的光标位置
 /// row 为 0基 行号 column 为 行内 utf-8 字节偏移量 0 基
+/// 客户端发送的 column 单位由 PositionEncoding 决定, 服务端内部统一转换为 utf-8 字节偏移量
 #[derive(Serialize, Deserialize, Debug, Copy, Clone)]
 pub struct Cursor {
     /// 光标所在的行号 0基
@@ -107,6 +148,61 @@ pub struct Cursor {
     pub column: usize,
 }
 
+/// 光标列位置的单位, 由客户端在握手时协商
+///
+/// VS Code 与 LSP 客户端使用 Utf16, 缺省为 Utf8 字节偏移量
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, Eq, PartialEq, Default)]
+pub enum PositionEncoding {
+    /// utf-8 字节
+    #[default]
+    Utf8,
+    /// utf-16 码元, 基本平面外的字符（如 emoji）占 2 个
+    Utf16,
+    /// Unicode 码点
+    Utf32,
+}
+impl PositionEncoding {
+    /// 将客户端光标转换为 行内 utf-8 字节偏移量
+    /// 落在字符内部时取该字符起始位置, 超出行尾时取行尾
+    pub fn to_byte_cursor(self, code: &str, cursor: &Cursor) -> Cursor {
+        if self == PositionEncoding::Utf8 {
+            return *cursor;
+        };
+        let line = PositionEncoding::_line(code, cursor.row);
+        let mut units = 0;
+        for (byte, ch) in line.char_indices() {
+            units += self._width(ch);
+            if units > cursor.column {
+                return Cursor { row: cursor.row, column: byte };
+            };
+        }
+        Cursor { row: cursor.row, column: line.len() }
+    }
+
+    /// 将 行内 utf-8 字节偏移量 转换为客户端使用的单位
+    pub fn to_client_cursor(self, code: &str, cursor: &Cursor) -> Cursor {
+        if self == PositionEncoding::Utf8 {
+            return *cursor;
+        };
+        let line = PositionEncoding::_line(code, cursor.row);
+        let column = line.char_indices().take_while(|(i, _)| *i < cursor.column).map(|(_, ch)| self._width(ch)).sum();
+        Cursor { row: cursor.row, column }
+    }
+
+    fn _width(self, ch: char) -> usize {
+        match self {
+            PositionEncoding::Utf8 => ch.len_utf8(),
+            PositionEncoding::Utf16 => ch.len_utf16(),
+            PositionEncoding::Utf32 => 1,
+        }
+    }
+
+    fn _line(code: &str, row: usize) -> &str {
+        // 与 tree-sitter 一致, 仅以 \n 分行
+        code.split('\n').nth(row).unwrap_or_default()
+    }
+}
+
 /// 静态资源文件打包
 /// 把 static 文件夹中的静态资源打包进 可执行文件中
 /// 通过API访问且无额外开销
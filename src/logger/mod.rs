@@ -0,0 +1,220 @@
+//! 日志
+//!
+//! stdout 仅用于输出端口号 与 认证令牌, 日志默认写入状态目录下的日志文件:
+//! `$XDG_STATE_HOME/lazy-input-switcher/server.log`（未设置时为 `~/.local/state/...`,
+//! Windows 下为 `%LOCALAPPDATA%\lazy-input-switcher\server.log`）, 也可通过参数输出至 stderr。
+//!
+//! 日志文件超过 MAX_LOG_FILE_SIZE 时轮转, 保留 MAX_LOG_FILES 个历史文件（server.log.1 ...）。
+//! 每行一个 json 对象:
+//! ```json
+//! {"ts_ms": 1760000000000, "level": "INFO", "module": "rpc", "message": "..."}
+//! ```
+//!
+//! 日志级别按模块（rpc / parser / switch / server ...）过滤, 规则写法与 env_logger 类似:
+//! `info,rpc=debug,switch=trace`, 运行时可通过 SetLogLevel 请求修改。
+
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::{Mutex, OnceLock, RwLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// 单个日志文件的最大字节数 (1 MiB)
+const MAX_LOG_FILE_SIZE: u64 = 1024 * 1024;
+/// 保留的历史日志文件数
+const MAX_LOG_FILES: usize = 3;
+
+/// 按模块过滤的日志级别
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub(crate) struct LogFilter {
+    default: LevelFilter,
+    modules: BTreeMap<String, LevelFilter>,
+}
+impl Default for LogFilter {
+    fn default() -> LogFilter {
+        LogFilter { default: LevelFilter::Info, modules: BTreeMap::new() }
+    }
+}
+impl LogFilter {
+    /// 在当前规则上应用新的规则, 同名模块的级别被覆盖
+    pub(crate) fn apply(&mut self, spec: &str) -> Result<(), String> {
+        let mut filter = self.clone();
+        for directive in spec.split(',').map(str::trim).filter(|d| !d.is_empty()) {
+            match directive.split_once('=') {
+                Some((module, level)) => {
+                    filter.modules.insert(module.trim().to_string(), LogFilter::_level(level)?);
+                },
+                None => filter.default = LogFilter::_level(directive)?,
+            }
+        }
+        *self = filter;
+        Ok(())
+    }
+
+    pub(crate) fn enabled(&self, module: &str, level: Level) -> bool {
+        level <= *self.modules.get(module).unwrap_or(&self.default)
+    }
+
+    /// 所有模块中最详细的级别, 用于 log 门面的快速过滤
+    pub(crate) fn max_level(&self) -> LevelFilter {
+        self.modules.values().copied().fold(self.default, std::cmp::max)
+    }
+
+    fn _level(level: &str) -> Result<LevelFilter, String> {
+        level.trim().parse().map_err(|_| format!("Invalid log level: {level}"))
+    }
+}
+impl FromStr for LogFilter {
+    type Err = String;
+
+    fn from_str(spec: &str) -> Result<LogFilter, String> {
+        let mut filter = LogFilter::default();
+        filter.apply(spec)?;
+        Ok(filter)
+    }
+}
+impl Display for LogFilter {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.default.as_str().to_lowercase())?;
+        for (module, level) in &self.modules {
+            write!(f, ",{module}={}", level.as_str().to_lowercase())?;
+        }
+        Ok(())
+    }
+}
+
+/// 按大小轮转的日志文件
+pub(crate) struct RotatingFile {
+    path: PathBuf,
+    file: File,
+    size: u64,
+    max_size: u64,
+    keep: usize,
+}
+impl RotatingFile {
+    pub(crate) fn open(path: &Path, max_size: u64, keep: usize) -> io::Result<RotatingFile> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        };
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let size = file.metadata()?.len();
+        Ok(RotatingFile { path: path.to_path_buf(), file, size, max_size, keep })
+    }
+
+    pub(crate) fn write_line(&mut self, line: &str) -> io::Result<()> {
+        if self.size > 0 && self.size + line.len() as u64 + 1 > self.max_size {
+            self._rotate()?;
+        };
+        writeln!(self.file, "{line}")?;
+        self.size += line.len() as u64 + 1;
+        Ok(())
+    }
+
+    fn _rotate(&mut self) -> io::Result<()> {
+        // server.log.{n-1} -> server.log.{n}, 最旧的文件被覆盖
+        for i in (1..self.keep).rev() {
+            let _ = fs::rename(self._history(i), self._history(i + 1));
+        }
+        if self.keep > 0 {
+            fs::rename(&self.path, self._history(1))?;
+        };
+        self.file = OpenOptions::new().create(true).write(true).truncate(true).open(&self.path)?;
+        self.size = 0;
+        Ok(())
+    }
+
+    fn _history(&self, index: usize) -> PathBuf {
+        let mut name = self.path.clone().into_os_string();
+        name.push(format!(".{index}"));
+        PathBuf::from(name)
+    }
+}
+
+/// 日志输出位置
+pub(crate) enum LogTarget {
+    File(PathBuf),
+    Stderr,
+}
+
+enum Sink {
+    File(RotatingFile),
+    Stderr,
+}
+
+struct Logger {
+    filter: RwLock<LogFilter>,
+    sink: Mutex<Sink>,
+}
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.filter.read().unwrap().enabled(module_of(metadata.target()), metadata.level())
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        };
+        let ts_ms = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_millis());
+        let line = serde_json::json!({
+            "ts_ms": ts_ms,
+            "level": record.level().as_str(),
+            "module": module_of(record.target()),
+            "message": record.args().to_string(),
+        }).to_string();
+        // 日志写入失败时无处报告, 直接忽略
+        let _ = match &mut *self.sink.lock().unwrap() {
+            Sink::File(file) => file.write_line(&line),
+            Sink::Stderr => writeln!(io::stderr(), "{line}"),
+        };
+    }
+
+    fn flush(&self) {}
+}
+
+static LOGGER: OnceLock<Logger> = OnceLock::new();
+
+/// 初始化全局日志, 只能调用一次
+pub(crate) fn init(filter: LogFilter, target: LogTarget) -> io::Result<()> {
+    let sink = match target {
+        LogTarget::File(path) => Sink::File(RotatingFile::open(&path, MAX_LOG_FILE_SIZE, MAX_LOG_FILES)?),
+        LogTarget::Stderr => Sink::Stderr,
+    };
+    let max_level = filter.max_level();
+    let logger = LOGGER.get_or_init(|| Logger { filter: RwLock::new(filter), sink: Mutex::new(sink) });
+    log::set_logger(logger).map_err(|e| io::Error::other(e.to_string()))?;
+    log::set_max_level(max_level);
+    Ok(())
+}
+
+/// 运行时修改日志级别, 返回修改后的完整规则
+pub(crate) fn set_filter(spec: &str) -> Result<String, String> {
+    let logger = LOGGER.get().ok_or("Logger is not initialized")?;
+    let mut filter = logger.filter.write().unwrap();
+    filter.apply(spec)?;
+    log::set_max_level(filter.max_level());
+    Ok(filter.to_string())
+}
+
+/// 默认日志文件位置
+pub(crate) fn default_log_path() -> PathBuf {
+    let env_dir = |name: &str| std::env::var_os(name).filter(|d| !d.is_empty()).map(PathBuf::from);
+    let base = if cfg!(target_os = "windows") {
+        env_dir("LOCALAPPDATA")
+    } else {
+        env_dir("XDG_STATE_HOME").or_else(|| env_dir("HOME").map(|home| home.join(".local").join("state")))
+    };
+    base.unwrap_or_else(std::env::temp_dir).join("lazy-input-switcher").join("server.log")
+}
+
+/// 日志所属模块: 指定了 target 时使用 target, 否则取模块路径中 crate 名之后的第一段
+pub(crate) fn module_of(target: &str) -> &str {
+    match target.split_once("::") {
+        Some((_, path)) => path.split("::").next().unwrap_or(path),
+        None if target == env!("CARGO_CRATE_NAME") => "main",
+        None => target,
+    }
+}
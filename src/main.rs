@@ -1,210 +1,116 @@
+mod config;
 mod core;
+mod logger;
 mod switch;
 mod parser;
 mod rpc;
+mod replay;
+mod server;
 #[cfg(test)]
 mod tests;
 
-use crate::core::{InputMethodMode, SupportLanguage};
-use crate::parser::Parser;
-use crate::rpc::*;
-use crate::switch::Switcher;
+use crate::config::Config;
+use crate::logger::LogTarget;
+use crate::rpc::AuthToken;
+use crate::server::{DiscoveryFile, Endpoint, Sever};
 
-use std::io;
 use std::io::{stdout, Write};
-use std::net::{TcpListener, TcpStream};
-use std::sync::atomic::{AtomicU16, Ordering};
-use std::time::{Duration, Instant};
-
-/// 若长时间无客户端连接则退出（秒）
-const IDLE_ACCEPT_TIMEOUT_SECS: u64 = 300;
+use std::sync::Arc;
 
 fn main() {
-    let mut server = Sever::new();
-    let (port, listener) = server.init_listener();
-    // 输出端口号并刷新stdout缓冲区
-    println!("{}", port);
-    stdout().flush().unwrap();
-    loop {
-        // 当客户端失去连接时，等待重连
-        let mut client = match server.accept_client(&listener) {
-            Ok(client) => client,
-            Err(_) => break,  // 超时结束监听
-        };
-        let cid = server.next_cid();
-        match server.handle_client(cid, &mut client) {
-            Ok(_) => break,  // 收到退出指令
-            _ => continue,
-        };
-    }
-    println!("Exiting server");
-}
-
-
-struct Sever {
-    switcher: Switcher,
-    parser: Parser,
-    current_cid: AtomicU16,
-}
-impl Sever {
-    fn new() -> Sever {
-        let switcher = match Switcher::new() {
-            Ok(s) => s,
-            Err(e) => {
-                panic!("Switcher init failed: {}", e);
-            }
-        };
-        let parser = Parser::new();
-        Sever { switcher, parser, current_cid: AtomicU16::new(1) }
-    }
-
-    fn init_listener(&self) -> (u16, TcpListener) {
-        match init_socket() {
-            Ok((p, l)) => (p, l),
-            Err(e) => { panic!("Not found available port! {e}") }
+    // 回放子命令: LazyInputSwitcher replay <trace.jsonl>
+    if std::env::args().nth(1).as_deref() == Some("replay") {
+        std::process::exit(replay::run(std::env::args().skip(2)));
+    };
+    let config = match Config::from_args(std::env::args().skip(1)) {
+        Ok(config) => config,
+        Err(e) => {
+            // stdout 仅用于输出端口号, 错误信息输出至 stderr
+            eprintln!("{e}");
+            std::process::exit(2);
         }
-    }
-
-    fn accept_client(&self, listener: &TcpListener) -> Result<TcpStream, io::Error> {
-        // 轮询监听，无连接睡眠，超时自动退出
-        listener.set_nonblocking(true).expect("Set non-blocking failed!");
-        let mut timeout = Duration::from_secs(IDLE_ACCEPT_TIMEOUT_SECS);
-        let mut deadline = Instant::now() + timeout;
-        loop {
-            match accept_connect(listener) {
-                Ok(stream) => {
-                    stream.set_nonblocking(false).expect("Set blocking failed!");
-                    listener.set_nonblocking(false).expect("Set blocking failed!");
-                    return Ok(stream);
-                }
-                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {
-                    if Instant::now() >= deadline {
-                        return Err(io::Error::new(io::ErrorKind::TimedOut, "accept timeout"))
-                    }
-                    std::thread::sleep(Duration::from_millis(50));
-                    continue;
-                }
-                Err(_) => {
-                    timeout = Duration::from_secs(IDLE_ACCEPT_TIMEOUT_SECS);
-                    deadline = Instant::now() + timeout;
-                    continue;
-                }
-            }
-        }
-    }
-
-    fn handle_client(&mut self, cid: u16, client: &mut TcpStream) -> io::Result<()> {
-        loop {
-            let message = recv_message(client)?;
-            let request = ClientRequest::from_json_message(message);
-            let response = match request {
-                Ok(req) => {
-                    match req.command {
-                        CommandMode::Analyze => self._grammar_analysis(cid, req),
-                        CommandMode::MethodOnly => self._method_only(cid, req),
-                        CommandMode::Switch => self._analyze_switch(cid, req),
-                        CommandMode::Exit => {
-                            return Ok(())
-                        },
-                    }
-                },
-                Err(err) => {
-                    ClientResponse::new(
-                        cid, false, Some(format!("Failed to analysis request! {err}")), None,
-                    )
-                }
-            };
-            send_message(client, response.to_json_message())?;
+    };
+    init_logger(&config);
+    // 令牌从文件读取时客户端已知晓, 仅随机生成的令牌需要输出
+    let print_token = config.token_file.is_none();
+    let token = match &config.token_file {
+        Some(path) => AuthToken::from_file(path),
+        None => AuthToken::generate(),
+    };
+    let token = match token {
+        Ok(token) => token,
+        Err(e) => {
+            log::error!("Failed to init auth token: {e}");
+            eprintln!("Failed to init auth token: {e}");
+            std::process::exit(2);
         }
-    }
-
-    fn next_cid(&self) -> u16 {
-        let next = self.current_cid.fetch_add(1, Ordering::Relaxed).wrapping_add(1);
-        if next == 0 {
-            self.next_cid()
-        } else {
-            next
-        }
-    }
-
-    fn _grammar_analysis(&mut self, cid: u16, req: ClientRequest) -> ClientResponse {
-        // Command::Analyze 请求响应
-
-        let params = match req.params.to_analyze_params() {
-            Ok(p) => p,
-            Err(e) => return ClientResponse::new(cid, false, Some(e.to_string()), None),
+    };
+    let discovery = config.daemon.then(|| {
+        DiscoveryFile::new(config.discovery_file.clone().unwrap_or_else(DiscoveryFile::default_path))
+    });
+    // 探测 到 写入发现文件期间持有排他锁, 同时启动的实例依次进行
+    let lock = discovery.as_ref().and_then(|d| match d.lock() {
+        Ok(lock) => Some(lock),
+        Err(e) => {
+            log::warn!("Failed to lock discovery file {}: {e}", d.path().display());
+            None
+        },
+    });
+    if let Some(endpoint) = discovery.as_ref().and_then(|d| d.find_live()) {
+        // 已有存活的守护进程, 输出其端点后退出, 编辑器直接连接该守护进程
+        log::info!("Found live daemon on port {} (pid {}), handing over", endpoint.port, endpoint.pid);
+        // 守护进程使用的令牌与令牌文件不一致时, 客户端无法得知, 同样需要输出
+        let differs = endpoint.token != token.as_str();
+        if differs && !print_token {
+            log::warn!("Live daemon uses a different token than the token file, printing its token");
         };
-
-        let language = SupportLanguage::from_string(&params.language);
-        if language.is_none() {
-            return ClientResponse::new(req.cid, false, Some("Unsupported language!".to_string()), None);
-        };
-        // 更新语法树 并判断 cursor 是否在 comment 节点内部
-        let language = language.unwrap();
-        self.parser.add_language(language);
-        self.parser.build_tree(language, &params.code);
-        let grammar = GrammarMode::from_bool(
-            self.parser.get_comments(language, &params.code).in_range(&params.cursor, &params.code)
-        );
-
-        let res = AnalyzeResult { grammar };
-        ClientResponse::new(cid, true, None, Some(CommandResult::from_analyze_result(res)))
-    }
-
-    fn _method_only(&mut self, cid: u16, req: ClientRequest) -> ClientResponse {
-        // 处理 Command::MethodOnly 请求响应
-
-        let params = match req.params.to_method_only_params() {
-            Ok(p) => p,
-            Err(e) => return ClientResponse::new(cid, false, Some(e.to_string()), None),
-        };
-        let target_mode = match InputMethodMode::from_str(params.mode) {
-            Ok(m) => m,
-            Err(e) => return ClientResponse::new(cid, false, Some(e.to_string()), None),
-        };
-        let success = self.switcher.switch(target_mode);
-        if success.is_err() {
-            return ClientResponse::new(cid, false, Some(success.err().unwrap().to_string()), None);
-        };
-
-        let res = match self.switcher.query() {
-            Ok(method) => MethodOnlyResult { method },
-            Err(e) => return ClientResponse::new(cid, false, Some(e.to_string()), None),
+        print_endpoint(endpoint.port, &endpoint.token, print_token || differs);
+        return;
+    };
+    let server = Arc::new(Sever::new(config, token.clone()));
+    let (port, listener) = server.init_listener();
+    let pid = std::process::id();
+    if let Some(discovery) = &discovery {
+        let endpoint = Endpoint { port, pid, token: token.as_str().to_string() };
+        if let Err(e) = discovery.write(&endpoint) {
+            log::warn!("Failed to write discovery file {}: {e}", discovery.path().display());
         };
-        ClientResponse::new(cid, true, None, Some(CommandResult::from_method_only_result(res)))
-    }
+    };
+    drop(lock);
+    log::info!("Listening on port {port} (pid {pid}, daemon: {})", discovery.is_some());
+    print_endpoint(port, token.as_str(), print_token);
+    // 收到退出指令、空闲超时 或 退出信号后结束监听, 监听在其他线程进行,
+    // 主线程运行输入法工作循环 (macOS 的输入法接口只能在主线程调用)
+    let serving = {
+        let server = server.clone();
+        std::thread::spawn(move || server.serve(&listener))
+    };
+    server.run_worker();
+    let reason = serving.join().expect("Server thread panicked");
+    if let Some(discovery) = &discovery {
+        discovery.remove(pid);
+    };
+    log::info!("Exiting server: {reason:?}");
+}
 
-    fn _analyze_switch(&mut self, cid: u16, request: ClientRequest) -> ClientResponse {
-        // 处理命令：需要 language、code、cursor
-        let params = match request.params.to_switch_params() {
-            Ok(p) => p,
-            Err(e) => return ClientResponse::new(cid, false, Some(e.to_string()), None),
-        };
-        let language = SupportLanguage::from_string(&params.language);
-        if language.is_none() {
-            return ClientResponse::new(request.cid, false, Some("Unsupported language!".to_string()), None);
-        };
+fn init_logger(config: &Config) {
+    // stdout 仅用于输出端口号 与 令牌, 日志文件不可用时退回 stderr
+    let target = match (config.log_stderr, &config.log_file) {
+        (true, _) => LogTarget::Stderr,
+        (false, Some(path)) => LogTarget::File(path.clone()),
+        (false, None) => LogTarget::File(logger::default_log_path()),
+    };
+    if let Err(e) = logger::init(config.log_filter.clone(), target) {
+        eprintln!("Failed to open log file, logging to stderr: {e}");
+        let _ = logger::init(config.log_filter.clone(), LogTarget::Stderr);
+    };
+}
 
-        let language = language.unwrap();
-        // 更新语法树 并判断 cursor 是否在 comment 节点内部
-        self.parser.add_language(language);
-        self.parser.build_tree(language, &params.code);
-        let comment = GrammarMode::from_bool(
-            self.parser.get_comments(language, &params.code).in_range(&params.cursor, &params.code)
-        );
-        // 根据 comment 决定是否切换输入法
-        let switch = match comment {
-            GrammarMode::Comment => { self.switcher.switch(InputMethodMode::Native) },
-            GrammarMode::Code => { self.switcher.switch(InputMethodMode::English) }
-        };
-        let error = match switch {
-            Ok(s) => {
-                if s { None } else { Some("Switch input method failed".to_string()) }
-            },
-            Err(e) => Option::from(e.to_string())
-        };
-        let input_method = self.switcher.query().unwrap_or_else(|_| InputMethodMode::English);
-        let res = SwitchResult { grammar: comment, method: input_method };
-        ClientResponse::new(cid, true, error, Some(CommandResult::from_switch_result(res)))
-    }
+fn print_endpoint(port: u16, token: &str, print_token: bool) {
+    // 输出端口号 与 认证令牌 并刷新stdout缓冲区
+    println!("{}", port);
+    if print_token {
+        println!("{}", token);
+    };
+    stdout().flush().unwrap();
 }
@@ -1,12 +1,16 @@
+use super::dynamic::GrammarRegistry;
 use crate::core::{SupportLanguage, StaticTreeSitterQuery as STSQuery};
 use std::collections::HashMap;
+use std::sync::Arc;
 use tree_sitter::{Language, Query};
 
 pub(super) struct Adapter {
     language: HashMap<SupportLanguage, Language>,
+    /// 运行时加载的语法
+    grammars: Arc<GrammarRegistry>,
 }
 impl Adapter {
-    pub(super) fn new() -> Adapter {
+    pub(super) fn new(grammars: Arc<GrammarRegistry>) -> Adapter {
         // 封装统一引用
         use tree_sitter_c::LANGUAGE as c_;
         use tree_sitter_lua::LANGUAGE as lua_;
@@ -16,12 +20,22 @@ impl Adapter {
         use tree_sitter_javascript::LANGUAGE as javascript_;
         use tree_sitter_kotlin_ng::LANGUAGE as kotlin_;
         use tree_sitter_typescript::LANGUAGE_TYPESCRIPT as typescript_;
+        use tree_sitter_typescript::LANGUAGE_TSX as tsx_;
+        use tree_sitter_ruby::LANGUAGE as ruby_;
+        use tree_sitter_swift::LANGUAGE as swift_;
+        use tree_sitter_elixir::LANGUAGE as elixir_;
         use tree_sitter_cpp::LANGUAGE as cpp_;
         use tree_sitter_go::LANGUAGE as go_;
         use tree_sitter_bash::LANGUAGE as bash_;
         use tree_sitter_sequel::LANGUAGE as sql_;
         use tree_sitter_php::LANGUAGE_PHP as php_;
         use tree_sitter_c_sharp::LANGUAGE as sharp_;
+        use tree_sitter_html::LANGUAGE as html_;
+        use tree_sitter_css::LANGUAGE as css_;
+        use tree_sitter_yaml::LANGUAGE as yaml_;
+        use tree_sitter_toml_ng::LANGUAGE as toml_;
+        use tree_sitter_json::LANGUAGE as json_;
+        use tree_sitter_md::{LANGUAGE as markdown_, INLINE_LANGUAGE as markdown_inline_};
 
         let mut language: HashMap<SupportLanguage, Language> = HashMap::new();
         language.insert(SupportLanguage::Rust, rust_.into());
@@ -38,19 +52,70 @@ impl Adapter {
         language.insert(SupportLanguage::Sql, sql_.into());
         language.insert(SupportLanguage::Php, php_.into());
         language.insert(SupportLanguage::CSharp, sharp_.into());
+        language.insert(SupportLanguage::Ruby, ruby_.into());
+        language.insert(SupportLanguage::Swift, swift_.into());
+        language.insert(SupportLanguage::Elixir, elixir_.into());
+        language.insert(SupportLanguage::Tsx, tsx_.into());
+        // JavaScript 语法本身支持 JSX
+        language.insert(SupportLanguage::Jsx, javascript_.into());
+        language.insert(SupportLanguage::Html, html_.into());
+        language.insert(SupportLanguage::Css, css_.into());
+        // 单文件组件 的 <script> / <style> 通过语言注入交由 JavaScript / TypeScript / CSS 解析
+        language.insert(SupportLanguage::Vue, html_.into());
+        language.insert(SupportLanguage::Svelte, html_.into());
+        language.insert(SupportLanguage::Yaml, yaml_.into());
+        language.insert(SupportLanguage::Toml, toml_.into());
+        language.insert(SupportLanguage::Json, json_.into());
+        language.insert(SupportLanguage::Markdown, markdown_.into());
+        language.insert(SupportLanguage::MarkdownInline, markdown_inline_.into());
 
-        Adapter { language }
+        Adapter { language, grammars }
+    }
+
+    /// 按名称查找运行时加载的语法
+    pub(super) fn find_dynamic(&self, name: &str) -> Option<SupportLanguage> {
+        self.grammars.find(name)
     }
 
     pub(super) fn get_language(&self, type_: SupportLanguage) -> &Language {
-        let res = self.language.get(&type_);
+        let res = match type_ {
+            SupportLanguage::Dynamic(name) => self.grammars.language(name),
+            _ => self.language.get(&type_),
+        };
         res.unwrap()
     }
 
     pub(super) fn get_comment_query(&self, type_: SupportLanguage) -> Query {
+        // 运行时加载的语法使用语法目录中的 query 文件, 加载时已检查过查询规则
+        if let SupportLanguage::Dynamic(name) = type_ {
+            return Query::new(self.get_language(type_), self.grammars.query(name).unwrap()).unwrap();
+        };
         // 加载 query 文件并 初始化 Query
-        let query_file = STSQuery::get(&format!("{}.scm", type_.to_string())).unwrap();
+        let query_code = Adapter::_read_query("", &type_.to_string()).unwrap();
+        Query::new(self.get_language(type_), &query_code).unwrap()
+    }
+
+    /// 加载语言注入规则 injections/<language>.scm, 不存在时返回 None
+    pub(super) fn get_injection_query(&self, type_: SupportLanguage) -> Option<Query> {
+        if let SupportLanguage::Dynamic(name) = type_ {
+            return Some(Query::new(self.get_language(type_), self.grammars.injection(name)?).unwrap());
+        };
+        let query_code = Adapter::_read_query("injections/", &type_.to_string())?;
+        Some(Query::new(self.get_language(type_), &query_code).unwrap())
+    }
+
+    /// 读取 query 文件, 首行为 `; inherits: html` 时先拼接同目录下被继承语言的规则
+    fn _read_query(dir: &str, name: &str) -> Option<String> {
+        let query_file = STSQuery::get(&format!("{dir}{name}.scm"))?;
         let query_code = std::str::from_utf8(&query_file.data).unwrap();
-        Query::new(self.get_language(type_), query_code).unwrap()
+        let mut code = String::new();
+        if let Some(inherits) = query_code.lines().next().and_then(|l| l.strip_prefix("; inherits:")) {
+            for parent in inherits.split(',') {
+                code.push_str(&Adapter::_read_query(dir, parent.trim()).unwrap());
+                code.push('\n');
+            }
+        };
+        code.push_str(query_code);
+        Some(code)
     }
 }
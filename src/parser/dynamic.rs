@@ -0,0 +1,115 @@
+//! 运行时加载的 Tree-sitter 语法
+//!
+//! 语法目录中的每个动态库 `<name>.so`（macOS 为 `.dylib`, Windows 为 `.dll`）导出标准的
+//! `tree_sitter_<name>` 符号, 并需要同目录下的查询文件 `<name>.scm`,
+//! 可选的 `injections/<name>.scm` 为注入规则。加载成功的语法以 `SupportLanguage::Dynamic(name)` 注册。
+//!
+//! 动态库中的代码会在服务端进程内执行, 语法目录必须是受信任的目录。
+
+use crate::core::SupportLanguage;
+use libloading::{Library, Symbol};
+use std::fs;
+use std::path::Path;
+use tree_sitter::{Language, Query};
+use tree_sitter_language::LanguageFn;
+
+struct DynamicGrammar {
+    name: &'static str,
+    language: Language,
+    query: String,
+    injection: Option<String>,
+}
+
+/// 运行时加载的语法, 由服务端创建后在所有连接间共享
+#[derive(Default)]
+pub(crate) struct GrammarRegistry {
+    grammars: Vec<DynamicGrammar>,
+    /// 语法的解析表位于动态库中, 动态库需与注册表同时存活
+    _libraries: Vec<Library>,
+}
+impl GrammarRegistry {
+    /// 加载目录下的所有语法, 无法加载的语法仅记录错误后跳过
+    pub(crate) fn load(dir: &Path) -> GrammarRegistry {
+        let mut registry = GrammarRegistry::default();
+        let entries = match fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(e) => {
+                log::error!("Failed to read grammar directory {}: {e}", dir.display());
+                return registry;
+            },
+        };
+        let mut names: Vec<String> = entries.filter_map(|entry| {
+            let path = entry.ok()?.path();
+            if path.extension()? != std::env::consts::DLL_EXTENSION {
+                return None;
+            };
+            path.file_stem()?.to_str().map(str::to_string)
+        }).collect();
+        names.sort();
+        for name in names {
+            if SupportLanguage::from_string(&name).is_some() || registry.find(&name).is_some() {
+                log::warn!("Skipped grammar {name}: language already registered");
+                continue;
+            };
+            match GrammarRegistry::_load_grammar(dir, &name) {
+                Ok((library, grammar)) => {
+                    log::info!("Loaded grammar {name} from {}", dir.display());
+                    registry.grammars.push(grammar);
+                    registry._libraries.push(library);
+                },
+                Err(e) => log::error!("Failed to load grammar {name}: {e}"),
+            }
+        }
+        registry
+    }
+
+    fn _load_grammar(dir: &Path, name: &str) -> Result<(Library, DynamicGrammar), String> {
+        if !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+            return Err("name must only contain ASCII letters, digits and '_'".to_string());
+        };
+        let query = fs::read_to_string(dir.join(format!("{name}.scm")))
+            .map_err(|e| format!("missing query file {name}.scm: {e}"))?;
+        let injection = fs::read_to_string(dir.join("injections").join(format!("{name}.scm"))).ok();
+
+        let path = dir.join(format!("{name}.{}", std::env::consts::DLL_EXTENSION));
+        // 加载受信任目录中的语法动态库, 符号签名与 tree-sitter 生成的解析器一致
+        let library = unsafe { Library::new(&path) }.map_err(|e| e.to_string())?;
+        let language: Language = unsafe {
+            let symbol: Symbol<unsafe extern "C" fn() -> *const ()> = library
+                .get(format!("tree_sitter_{name}").as_bytes())
+                .map_err(|e| e.to_string())?;
+            LanguageFn::from_raw(*symbol).into()
+        };
+
+        // 提前检查 ABI 版本 与 查询规则, 避免使用时失败
+        tree_sitter::Parser::new().set_language(&language).map_err(|e| e.to_string())?;
+        Query::new(&language, &query).map_err(|e| format!("invalid {name}.scm: {e}"))?;
+        if let Some(injection) = &injection {
+            Query::new(&language, injection).map_err(|e| format!("invalid injections/{name}.scm: {e}"))?;
+        };
+        // 语言名称在进程生命周期内有效
+        let name: &'static str = Box::leak(name.to_lowercase().into_boxed_str());
+        Ok((library, DynamicGrammar { name, language, query, injection }))
+    }
+
+    /// 按名称查找已加载的语法, 不区分大小写
+    pub(super) fn find(&self, name: &str) -> Option<SupportLanguage> {
+        self._get(name).map(|g| SupportLanguage::Dynamic(g.name))
+    }
+
+    pub(super) fn language(&self, name: &str) -> Option<&Language> {
+        self._get(name).map(|g| &g.language)
+    }
+
+    pub(super) fn query(&self, name: &str) -> Option<&str> {
+        self._get(name).map(|g| g.query.as_str())
+    }
+
+    pub(super) fn injection(&self, name: &str) -> Option<&str> {
+        self._get(name).and_then(|g| g.injection.as_deref())
+    }
+
+    fn _get(&self, name: &str) -> Option<&DynamicGrammar> {
+        self.grammars.iter().find(|g| g.name.eq_ignore_ascii_case(name))
+    }
+}
@@ -1,68 +1,306 @@
 
 mod adapter;
+mod dynamic;
 
 use crate::core::*;
 use adapter::*;
+pub(crate) use dynamic::GrammarRegistry;
 use std::collections::HashMap;
-use tree_sitter::{Node, Query, QueryCursor, Range, StreamingIterator, Tree};
+use std::sync::Arc;
+use tree_sitter::{Node, Point, Query, QueryCursor, Range, StreamingIterator, Tree};
+
+/// 语言注入的最大嵌套层数, 避免注入规则相互引用时无限递归
+const MAX_INJECTION_DEPTH: usize = 3;
 
 pub(super) struct Parser {
     adapter: Adapter,
     tree: Option<Tree>,
     parsers: HashMap<SupportLanguage, tree_sitter::Parser>,
     query: HashMap<SupportLanguage, Query>,
+    /// 语言注入规则, 没有注入规则的语言为 None
+    injections: HashMap<SupportLanguage, Option<Query>>,
 }
 impl Parser {
-    pub(super) fn new() -> Parser {
-        let adapter = Adapter::new();
+    /// grammars 为运行时加载的语法, 与内置语法一同使用
+    pub(super) fn new(grammars: Arc<GrammarRegistry>) -> Parser {
+        let adapter = Adapter::new(grammars);
         let parsers = HashMap::new();
         let query = HashMap::new();
-        Parser { adapter, parsers, query, tree: None }
+        let injections = HashMap::new();
+        Parser { adapter, parsers, query, injections, tree: None }
+    }
+
+    /// 按名称查找内置 或 运行时加载的语言
+    pub(super) fn language(&self, name: &str) -> Option<SupportLanguage> {
+        SupportLanguage::from_string(name).or_else(|| self.adapter.find_dynamic(name))
     }
 
     pub(super) fn add_language(&mut self, type_: SupportLanguage) {
+        // 语法 与 查询只需加载一次
+        if self.parsers.contains_key(&type_) {
+            return;
+        };
         let mut parser = tree_sitter::Parser::new();
         let query = self.adapter.get_comment_query(type_);
+        let injection = self.adapter.get_injection_query(type_);
 
         parser.set_language(self.adapter.get_language(type_)).unwrap();
         self.parsers.insert(type_, parser);
         self.query.insert(type_, query);
+        self.injections.insert(type_, injection);
+        log::debug!("Loaded {type_} grammar");
     }
 
-    pub(super) fn build_tree(&mut self, type_: SupportLanguage, code: &String) {
+    pub(super) fn build_tree(&mut self, type_: SupportLanguage, code: &str) {
         // 如果tree不存在，则自动新建树
         let parser = self.parsers.get_mut(&type_).unwrap();
         self.tree = parser.parse(code.as_bytes(), None);
+        match &self.tree {
+            Some(_) => log::trace!("Parsed {} bytes of {type_}", code.len()),
+            None => log::warn!("Failed to parse {type_} code"),
+        };
     }
 
-    pub(super) fn get_comments(&mut self, type_: SupportLanguage, code: &String) -> NodesRange {
+    /// 复用已缓存的语法树, 跳过重复解析
+    pub(super) fn set_tree(&mut self, tree: Tree) {
+        self.tree = Some(tree);
+    }
+
+    pub(super) fn tree(&self) -> Option<&Tree> {
+        self.tree.as_ref()
+    }
+
+    pub(super) fn get_comments(&mut self, type_: SupportLanguage, code: &str) -> NodesRange {
+        match self.tree.clone() {
+            Some(tree) => self._comments(type_, &tree, code, 0),
+            None => NodesRange::new(),
+        }
+    }
+
+    fn _comments(&mut self, type_: SupportLanguage, tree: &Tree, code: &str, depth: usize) -> NodesRange {
         let mut node_range = NodesRange::new();
-        if let Some(tree) = &self.tree {
-            let root = tree.root_node();
-            let query = self.query.get(&type_).unwrap();
-            let mut query_cursor = QueryCursor::new();
-            let mut res = query_cursor.matches(&query, root, code.as_bytes());
-            // 遍历结果，返回comment的range数组
-            while let Some(m) = res.next() {
-                for iter in m.captures { node_range.add_node(iter.node) };
+        let root = tree.root_node();
+        let query = self.query.get(&type_).unwrap();
+        let names = query.capture_names();
+        let mut query_cursor = QueryCursor::new();
+        let mut res = query_cursor.matches(query, root, code.as_bytes());
+        // 遍历结果，返回comment的range数组, 以 _ 开头等其他捕获仅用于谓词
+        while let Some(m) = res.next() {
+            let settings = query.property_settings(m.pattern_index);
+            let setting = |key: &str| settings.iter().find(|p| &*p.key == key).and_then(|p| p.value.as_deref());
+            for iter in m.captures {
+                let kind = match names[iter.index as usize] {
+                    "comment" => ContextKind::Comment,
+                    "prose" => ContextKind::Prose,
+                    "code" => ContextKind::Code,
+                    _ => continue,
+                };
+                node_range.add_node(iter.node, kind);
+                // 节点文本中的插值表达式为代码, 如 Vue 模板中的 {{ msg }}
+                if let (Some(open), Some(close)) = (setting("interpolation.start"), setting("interpolation.end")) {
+                    node_range.add_interpolations(iter.node, code, open, close);
+                };
             };
         };
+        if depth >= MAX_INJECTION_DEPTH {
+            return node_range;
+        };
+        // 注入区间使用对应语言单独解析, 位于宿主注释内的注入区间（如 Python 文档字符串）被忽略
+        for (language, ranges) in self._injections(type_, tree, code) {
+            let in_comment = |r: &Range| node_range.nodes_range.iter()
+                .any(|(c, kind)| *kind == ContextKind::Comment && c.start_byte <= r.start_byte && r.end_byte <= c.end_byte);
+            if ranges.iter().any(in_comment) {
+                continue;
+            };
+            if let Some(injected) = self._parse_ranges(language, &ranges, code) {
+                log::trace!("Injected {language} into {type_} at {} range(s)", ranges.len());
+                let comments = self._comments(language, &injected, code, depth + 1);
+                node_range.injections.push(Injection { ranges, comments });
+            };
+        }
         node_range
     }
+
+    /// 执行注入规则, 返回注入的语言 与 区间
+    /// 注入语言由 #set! injection.language 或 @injection.language 捕获的文本指定, 不支持的语言被忽略;
+    /// 带有 injection.combined 的规则中, 同一语言的所有区间合并为一次解析
+    fn _injections(&self, type_: SupportLanguage, tree: &Tree, code: &str) -> Vec<(SupportLanguage, Vec<Range>)> {
+        let Some(Some(query)) = self.injections.get(&type_) else {
+            return vec![];
+        };
+        let Some(content) = query.capture_index_for_name("injection.content") else {
+            return vec![];
+        };
+        let language_capture = query.capture_index_for_name("injection.language");
+        let mut injections: Vec<(SupportLanguage, Vec<Range>)> = Vec::new();
+        let mut combined: HashMap<(usize, SupportLanguage), usize> = HashMap::new();
+        let mut query_cursor = QueryCursor::new();
+        let mut res = query_cursor.matches(query, tree.root_node(), code.as_bytes());
+        while let Some(m) = res.next() {
+            let settings = query.property_settings(m.pattern_index);
+            let setting = |key: &str| settings.iter().find(|p| &*p.key == key);
+            let name = setting("injection.language").and_then(|p| p.value.as_deref().map(str::to_string)).or_else(|| {
+                let capture = m.captures.iter().find(|c| Some(c.index) == language_capture)?;
+                capture.node.utf8_text(code.as_bytes()).ok().map(str::to_string)
+            });
+            let language = name.as_deref().and_then(|n| injection_language(n).or_else(|| self.adapter.find_dynamic(n)));
+            let Some(language) = language else {
+                continue;
+            };
+            let ranges = m.captures.iter().filter(|c| c.index == content).map(|c| c.node.range());
+            match setting("injection.combined") {
+                Some(_) => {
+                    let index = *combined.entry((m.pattern_index, language)).or_insert_with(|| {
+                        injections.push((language, vec![]));
+                        injections.len() - 1
+                    });
+                    injections[index].1.extend(ranges);
+                },
+                None => injections.push((language, ranges.collect())),
+            }
+        }
+        for (_, ranges) in &mut injections {
+            ranges.sort_by_key(|r| r.start_byte);
+            ranges.dedup_by_key(|r| r.start_byte);
+        }
+        injections.retain(|(_, ranges)| !ranges.is_empty());
+        injections
+    }
+
+    /// 仅解析指定区间内的代码
+    fn _parse_ranges(&mut self, language: SupportLanguage, ranges: &[Range], code: &str) -> Option<Tree> {
+        self.add_language(language);
+        let parser = self.parsers.get_mut(&language).unwrap();
+        if let Err(e) = parser.set_included_ranges(ranges) {
+            log::debug!("Invalid {language} injection ranges: {e}");
+            return None;
+        };
+        let tree = parser.parse(code.as_bytes(), None);
+        parser.set_included_ranges(&[]).unwrap();
+        tree
+    }
+}
+
+/// 注入规则中的语言名称, 兼容 Markdown 代码块中常见的简写
+fn injection_language(name: &str) -> Option<SupportLanguage> {
+    match name.trim().to_lowercase().as_str() {
+        "rs" => Some(SupportLanguage::Rust),
+        "py" | "python3" => Some(SupportLanguage::Python),
+        "js" | "mjs" | "cjs" => Some(SupportLanguage::JavaScript),
+        "ts" => Some(SupportLanguage::TypeScript),
+        "kt" | "kts" => Some(SupportLanguage::Kotlin),
+        "c++" | "cc" | "cxx" | "hpp" => Some(SupportLanguage::Cpp),
+        "h" => Some(SupportLanguage::C),
+        "golang" => Some(SupportLanguage::Go),
+        "sh" | "shell" | "zsh" => Some(SupportLanguage::Bash),
+        "cs" | "c#" => Some(SupportLanguage::CSharp),
+        "md" => Some(SupportLanguage::Markdown),
+        "rb" => Some(SupportLanguage::Ruby),
+        "ex" | "exs" => Some(SupportLanguage::Elixir),
+        "yml" => Some(SupportLanguage::Yaml),
+        "htm" | "xhtml" => Some(SupportLanguage::Html),
+        "scss" | "less" | "postcss" => Some(SupportLanguage::Css),
+        name => SupportLanguage::from_string(name),
+    }
+}
+
+/// 上下文类型
+/// Prose 为 Markdown 等文档中的正文, 与注释一样使用母语输入
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub(super) enum ContextKind {
+    Code,
+    Comment,
+    Prose,
+}
+
+/// 文档中的一段 代码、注释 或 正文 上下文
+#[derive(Debug, Clone, Copy)]
+pub(super) struct Context {
+    pub(super) kind: ContextKind,
+    pub(super) range: Range,
+}
+impl Context {
+    fn _span(kind: ContextKind, start: (usize, Point), end: (usize, Point)) -> Context {
+        let range = Range { start_byte: start.0, end_byte: end.0, start_point: start.1, end_point: end.1 };
+        Context { kind, range }
+    }
+
+    /// 截取位于 range 内的部分
+    fn _clip(&self, range: &Range) -> Option<Context> {
+        let start = if self.range.start_byte >= range.start_byte {
+            (self.range.start_byte, self.range.start_point)
+        } else {
+            (range.start_byte, range.start_point)
+        };
+        let end = if self.range.end_byte <= range.end_byte {
+            (self.range.end_byte, self.range.end_point)
+        } else {
+            (range.end_byte, range.end_point)
+        };
+        (start.0 < end.0).then(|| Context::_span(self.kind, start, end))
+    }
+}
+
+/// 注入的其他语言, 区间内按注入语言的节点判断上下文
+struct Injection {
+    ranges: Vec<Range>,
+    comments: NodesRange,
 }
 
+/// Query 捕获的节点区间
+/// @comment 为注释, @prose 为正文, @code 用于在正文中标记代码（如 Markdown 行内代码）
+/// 节点相互嵌套时 由包含 cursor 的最内层节点决定上下文
 pub(super) struct NodesRange {
-    nodes_range: Vec<Range>,
+    nodes_range: Vec<(Range, ContextKind)>,
+    injections: Vec<Injection>,
 }
 impl NodesRange {
-    fn new() -> NodesRange { NodesRange { nodes_range: vec![] } }
+    fn new() -> NodesRange { NodesRange { nodes_range: vec![], injections: vec![] } }
 
-    fn add_node(&mut self, node: Node) {
-        self.nodes_range.push(node.range())
+    fn add_node(&mut self, node: Node, kind: ContextKind) {
+        self.nodes_range.push((node.range(), kind))
     }
 
-    pub(super) fn in_range(&self, cursor: &Cursor, code: &String) -> bool {
-        // 判断cursor的位置是否在node节点里
+    /// 将节点文本中 open 与 close 之间（包含两端）的部分标记为代码
+    fn add_interpolations(&mut self, node: Node, code: &str, open: &str, close: &str) {
+        let start = node.start_byte();
+        let Some(text) = code.get(start..node.end_byte()) else {
+            return;
+        };
+        let point = |offset: usize| {
+            let prefix = &text[..offset];
+            match prefix.rfind('\n') {
+                Some(i) => Point { row: node.start_position().row + prefix.matches('\n').count(), column: offset - i - 1 },
+                None => Point { row: node.start_position().row, column: node.start_position().column + offset },
+            }
+        };
+        let mut offset = 0;
+        while let Some(i) = text[offset..].find(open) {
+            let from = offset + i;
+            let Some(j) = text[from + open.len()..].find(close) else {
+                break;
+            };
+            let to = from + open.len() + j + close.len();
+            let range = Range { start_byte: start + from, end_byte: start + to, start_point: point(from), end_point: point(to) };
+            self.nodes_range.push((range, ContextKind::Code));
+            offset = to;
+        }
+    }
+
+    /// cursor 所在的上下文类型
+    pub(super) fn kind_at(&self, cursor: &Cursor, code: &str) -> ContextKind {
+        // cursor 位于注入区间时 由注入的语言判断
+        let point = Point { row: cursor.row, column: cursor.column };
+        let injection = self.injections.iter()
+            .find(|i| i.ranges.iter().any(|r| r.start_point <= point && point <= r.end_point));
+        match injection {
+            Some(injection) => injection.comments.kind_at(cursor, code),
+            None => self._own_kind(cursor, code),
+        }
+    }
+
+    fn _own_kind(&self, cursor: &Cursor, code: &str) -> ContextKind {
+        // 判断cursor的位置是否在node节点里, 多个节点包含 cursor 时取最短的节点
         // row 为 0基 行号 column 为 行内 utf-8 字节偏移量 0 基
         let (sr, sc) = (cursor.row, cursor.column);
 
@@ -75,12 +313,16 @@ impl NodesRange {
             if c1 > c2 { return 1 };
             0
         }
-        for range in &self.nodes_range {
+        let contains = |range: &Range, kind: ContextKind| {
             let start = range.start_point;
             let end = range.end_point;
             let (rs, cs) = (start.row, start.column);
             let (re, ce) = (end.row, end.column);
 
+            // 正文为闭区间, 光标位于段落开头 或 结尾时仍在正文中
+            if kind == ContextKind::Prose {
+                return cmp_pos(sr, sc, rs, cs) >= 0 && cmp_pos(sr, sc, re, ce) <= 0;
+            };
             // 严格判断边界条件， 左开右闭
             // 注意TreeSitter本身范围为 左闭右开区间
             //
@@ -101,10 +343,143 @@ impl NodesRange {
                     }
                 }
                 return true;
-            } else if cmp_pos(sr, sc, rs, cs) > 0 && cmp_pos(sr, sc, re, ce) < 0 {
-                return true;
+            };
+            cmp_pos(sr, sc, rs, cs) > 0 && cmp_pos(sr, sc, re, ce) < 0
+        };
+        // 区间相同时后捕获的节点优先, 与 _own_contexts 一致
+        self.nodes_range.iter()
+            .filter(|(range, kind)| contains(range, *kind))
+            .rev()
+            .min_by_key(|(range, _)| range.end_byte - range.start_byte)
+            .map_or(ContextKind::Code, |(_, kind)| *kind)
+    }
+
+    /// 包含 cursor 的上下文区间, 与 kind_at 的判断结果一致
+    /// cursor 位于区间端点时, 端点是否属于该区间由 includes 判断
+    /// 找不到时（如两段注释首尾相接）返回位于 cursor 处的空区间
+    pub(super) fn enclosing(&self, cursor: &Cursor, code: &str) -> Context {
+        let kind = self.kind_at(cursor, code);
+        let point = Point { row: cursor.row, column: cursor.column };
+        // 相邻区间类型不同, 类型相同且包含 cursor 的区间唯一
+        let found = self.contexts(code).into_iter()
+            .find(|c| c.kind == kind && c.range.start_point <= point && point <= c.range.end_point);
+        found.unwrap_or_else(|| {
+            let byte = NodesRange::_byte_of(code, point);
+            let range = Range { start_byte: byte, end_byte: byte, start_point: point, end_point: point };
+            Context { kind, range }
+        })
+    }
+
+    /// 上下文区间的 起始 与 结束位置 是否属于该区间, 与 kind_at 在端点处的判断结果一致
+    /// 如注释之后同一行仍有代码时, 注释的结束位置属于其后的代码区间
+    pub(super) fn includes(&self, context: &Context, code: &str) -> (bool, bool) {
+        let at = |point: Point| self.kind_at(&Cursor { row: point.row, column: point.column }, code) == context.kind;
+        (at(context.range.start_point), at(context.range.end_point))
+    }
+
+    /// 将整个文档划分为按位置排序、相邻类型不同的上下文区间
+    /// 注入区间内使用注入语言的上下文
+    pub(super) fn contexts(&self, code: &str) -> Vec<Context> {
+        let own = self._own_contexts(code);
+        if self.injections.is_empty() {
+            return own;
+        };
+        let mut ranges: Vec<&Range> = self.injections.iter().flat_map(|i| &i.ranges).collect();
+        ranges.sort_by_key(|r| r.start_byte);
+        let mut pieces = Vec::new();
+        // 宿主语言的上下文去除注入区间
+        for context in own {
+            let mut start = (context.range.start_byte, context.range.start_point);
+            for range in &ranges {
+                if range.end_byte <= start.0 || range.start_byte >= context.range.end_byte {
+                    continue;
+                };
+                if range.start_byte > start.0 {
+                    pieces.push(Context::_span(context.kind, start, (range.start_byte, range.start_point)));
+                };
+                start = (range.end_byte, range.end_point);
             }
+            if context.range.end_byte > start.0 {
+                pieces.push(Context::_span(context.kind, start, (context.range.end_byte, context.range.end_point)));
+            };
+        }
+        for injection in &self.injections {
+            let inner = injection.comments.contexts(code);
+            for range in &injection.ranges {
+                pieces.extend(inner.iter().filter_map(|c| c._clip(range)));
+            }
+        }
+        pieces.sort_by_key(|c| c.range.start_byte);
+        let mut contexts = Vec::with_capacity(pieces.len());
+        for piece in pieces {
+            NodesRange::_push_context(&mut contexts, piece);
+        }
+        contexts
+    }
+
+    /// 仅由本语言节点划分的上下文区间, 内层节点覆盖外层节点, 同类型的重叠节点合并
+    fn _own_contexts(&self, code: &str) -> Vec<Context> {
+        let mut nodes = self.nodes_range.clone();
+        nodes.sort_by_key(|(r, _)| (r.start_byte, std::cmp::Reverse(r.end_byte)));
+
+        let mut contexts = Vec::with_capacity(nodes.len() * 2 + 1);
+        // 当前位置 与 包含当前位置的节点栈
+        let mut position = (0, Point::default());
+        let mut stack: Vec<(usize, Point, ContextKind)> = Vec::new();
+        let emit = |contexts: &mut Vec<Context>, position: &mut (usize, Point), end: (usize, Point), kind| {
+            if end.0 > position.0 {
+                NodesRange::_push_context(contexts, Context::_span(kind, *position, end));
+                *position = end;
+            };
         };
-        false
+        for (range, kind) in nodes {
+            while let Some(&(end, end_point, top)) = stack.last() && end <= range.start_byte {
+                emit(&mut contexts, &mut position, (end, end_point), top);
+                stack.pop();
+            }
+            let current = stack.last().map_or(ContextKind::Code, |top| top.2);
+            emit(&mut contexts, &mut position, (range.start_byte, range.start_point), current);
+            let mut end = (range.end_byte, range.end_point);
+            if let Some(top) = stack.last_mut() && end.0 > top.0 {
+                // 与外层节点部分重叠: 同类型时合并, 否则截断至外层节点结束位置
+                if top.2 == kind {
+                    (top.0, top.1) = end;
+                    continue;
+                };
+                end = (top.0, top.1);
+            };
+            stack.push((end.0, end.1, kind));
+        }
+        while let Some((end, end_point, kind)) = stack.pop() {
+            emit(&mut contexts, &mut position, (end, end_point), kind);
+        }
+        emit(&mut contexts, &mut position, (code.len(), NodesRange::_end_point(code)), ContextKind::Code);
+        if contexts.is_empty() {
+            contexts.push(Context::_span(ContextKind::Code, position, position));
+        };
+        contexts
+    }
+
+    /// 追加上下文区间, 与前一个区间首尾相接 且 类型相同时合并
+    fn _push_context(contexts: &mut Vec<Context>, context: Context) {
+        match contexts.last_mut() {
+            Some(last) if last.kind == context.kind && last.range.end_byte == context.range.start_byte => {
+                last.range.end_byte = context.range.end_byte;
+                last.range.end_point = context.range.end_point;
+            },
+            _ => contexts.push(context),
+        }
+    }
+
+    fn _end_point(code: &str) -> Point {
+        // tree-sitter 仅以 \n 作为换行符, column 为行内字节偏移量
+        let row = code.matches('\n').count();
+        let column = code.len() - code.rfind('\n').map_or(0, |i| i + 1);
+        Point { row, column }
+    }
+
+    fn _byte_of(code: &str, point: Point) -> usize {
+        let line: usize = code.split_inclusive('\n').take(point.row).map(str::len).sum();
+        (line + point.column).min(code.len())
     }
 }
@@ -0,0 +1,205 @@
+//! trace 回放
+//!
+//! ```bash
+//! LazyInputSwitcher replay <trace.jsonl>
+//! ```
+//! 使用模拟输入法后端在本进程内启动服务端, 按连接依次重放 trace 中记录的请求,
+//! 并与记录的响应比较, 输出不一致的请求。
+//!
+//! - 同一连接内的请求按顺序发送, 每个请求收到响应后再发送下一个, 不同连接依次回放
+//! - 比较时忽略 cid 与错误描述 message, 只比较 success、错误码、附加数据 与 结果;
+//!   Status 的结果与运行环境相关, 仅比较 success
+//! - 记录中的 cid 按回放时服务端分配的 cid 替换, 保证会话缓存 与 订阅一致
+//! - 通知与时序相关, 不参与比较
+//! - Exit 请求 与 已脱敏的请求不会被回放
+
+use crate::config::Config;
+use crate::rpc::*;
+use crate::server::{Sever, StopReason};
+
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io;
+use std::net::TcpStream;
+use std::path::Path;
+use std::sync::Arc;
+use std::thread;
+
+/// 回放结果与记录不一致的请求
+pub(crate) struct ReplayDiff {
+    pub(crate) conn: u64,
+    /// 请求在该连接中的序号, 从 1 开始
+    pub(crate) index: usize,
+    pub(crate) command: String,
+    pub(crate) expected: Value,
+    pub(crate) actual: Value,
+}
+
+#[derive(Default)]
+pub(crate) struct ReplayReport {
+    pub(crate) replayed: usize,
+    pub(crate) skipped: usize,
+    pub(crate) diffs: Vec<ReplayDiff>,
+}
+
+/// 单个请求 与 记录的响应
+struct Step {
+    request: Value,
+    redacted: bool,
+    expected: Option<Value>,
+}
+
+/// replay 子命令入口, 返回进程退出码: 0 一致, 1 存在差异, 2 回放失败
+pub(crate) fn run(args: impl IntoIterator<Item = String>) -> i32 {
+    let args: Vec<String> = args.into_iter().collect();
+    let [path] = args.as_slice() else {
+        eprintln!("Usage: LazyInputSwitcher replay <trace.jsonl>");
+        return 2;
+    };
+    let report = match replay(Path::new(path)) {
+        Ok(report) => report,
+        Err(e) => {
+            eprintln!("Replay failed: {e}");
+            return 2;
+        }
+    };
+    for diff in &report.diffs {
+        println!("conn {} #{} {}:", diff.conn, diff.index, diff.command);
+        println!("  expected: {}", diff.expected);
+        println!("  actual:   {}", diff.actual);
+    }
+    println!(
+        "Replayed {} requests, skipped {}, {} diff(s)", report.replayed, report.skipped, report.diffs.len(),
+    );
+    if report.diffs.is_empty() { 0 } else { 1 }
+}
+
+/// 回放 trace 文件
+pub(crate) fn replay(path: &Path) -> io::Result<ReplayReport> {
+    let connections = load_trace(path)?;
+
+    let config = Config { mock_switcher: true, idle_timeout: None, ..Config::default() };
+    let token = AuthToken::generate()?;
+    let server = Arc::new(Sever::new(config.clone(), token.clone()));
+    let (port, listener) = server.init_listener();
+    let serving = thread::spawn(move || server.serve(&listener));
+
+    let mut report = ReplayReport::default();
+    for (conn, steps) in connections {
+        let mut client = TcpStream::connect(("127.0.0.1", port))?;
+        let mut encoding = Encoding::default();
+        let mut first = true;
+        // 记录中的 cid -> 回放时分配的 cid
+        let mut cids: HashMap<Value, Value> = HashMap::new();
+        for (index, step) in steps.into_iter().enumerate() {
+            let command = step.request.get("command").and_then(Value::as_str).unwrap_or_default().to_string();
+            // Exit 会结束回放用的服务端, 脱敏的请求缺少代码原文
+            if command == "Exit" || step.redacted {
+                report.skipped += 1;
+                continue;
+            };
+            let mut request = step.request;
+            if let Some(object) = request.as_object_mut() {
+                if first {
+                    object.insert("token".to_string(), Value::String(token.as_str().to_string()));
+                };
+                if let Some(cid) = object.get_mut("cid") && let Some(mapped) = cids.get(cid) {
+                    *cid = mapped.clone();
+                };
+            };
+            first = false;
+            send_message(&mut client, &encoding.encode(&request))?;
+            let actual = recv_response(&mut client, &config, encoding)?;
+            report.replayed += 1;
+            // 握手成功后 后续消息使用协商后的编码
+            if command == "Handshake" && let Some(negotiated) = actual.pointer("/result/encoding") {
+                encoding = serde_json::from_value(negotiated.clone()).unwrap_or(encoding);
+            };
+            if let Some(expected) = &step.expected && let (Some(from), Some(to)) = (expected.get("cid"), actual.get("cid")) {
+                cids.insert(from.clone(), to.clone());
+            };
+            if let Some(expected) = step.expected && normalize(&command, &expected) != normalize(&command, &actual) {
+                report.diffs.push(ReplayDiff { conn, index: index + 1, command, expected, actual });
+            };
+        }
+    }
+
+    // 结束回放用的服务端
+    let mut client = TcpStream::connect(("127.0.0.1", port))?;
+    let exit = serde_json::json!({ "token": token.as_str(), "cid": 0, "command": "Exit", "params": null });
+    send_message(&mut client, &Encoding::Json.encode(&exit))?;
+    if serving.join().ok() != Some(StopReason::Exit) {
+        return Err(io::Error::other("replay server did not exit"));
+    };
+    Ok(report)
+}
+
+/// 读取 trace 文件, 按连接分组, 并为每个请求匹配记录的响应
+fn load_trace(path: &Path) -> io::Result<Vec<(u64, Vec<Step>)>> {
+    let mut connections: Vec<(u64, Vec<TraceRecord>)> = Vec::new();
+    for (number, line) in fs::read_to_string(path)?.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        };
+        let record: TraceRecord = serde_json::from_str(line).map_err(|e| {
+            io::Error::new(io::ErrorKind::InvalidData, format!("line {}: {e}", number + 1))
+        })?;
+        match connections.iter_mut().find(|(conn, _)| *conn == record.conn) {
+            Some((_, records)) => records.push(record),
+            None => connections.push((record.conn, vec![record])),
+        }
+    }
+    Ok(connections.into_iter().map(|(conn, records)| (conn, match_responses(records))).collect())
+}
+
+fn match_responses(records: Vec<TraceRecord>) -> Vec<Step> {
+    // 响应可能乱序, 有 id 的请求按 id 匹配, 否则匹配其后第一个未被认领的响应
+    let mut claimed = HashSet::new();
+    let mut steps = Vec::new();
+    for (i, record) in records.iter().enumerate() {
+        if record.kind != TraceKind::Request {
+            continue;
+        };
+        let id = record.message.get("id").filter(|id| !id.is_null());
+        let response = records.iter().enumerate().skip(i + 1).find(|(j, r)| {
+            r.kind == TraceKind::Response && !claimed.contains(j) && match id {
+                Some(id) => r.message.get("id") == Some(id),
+                None => true,
+            }
+        });
+        let expected = response.map(|(j, r)| {
+            claimed.insert(j);
+            r.message.clone()
+        });
+        steps.push(Step { request: record.message.clone(), redacted: record.redacted, expected });
+    }
+    steps
+}
+
+fn recv_response(client: &mut TcpStream, config: &Config, encoding: Encoding) -> io::Result<Value> {
+    loop {
+        let message = recv_message(client, config.max_frame_size, None, config.read_timeout)
+            .map_err(|e| io::Error::other(e.to_string()))?;
+        let value: Value = encoding.decode(&message).map_err(io::Error::other)?;
+        // 通知与时序相关, 跳过
+        if value.get("notification").is_none() {
+            return Ok(value);
+        };
+    }
+}
+
+/// 去除与回放环境相关的字段
+fn normalize(command: &str, response: &Value) -> Value {
+    let mut response = response.clone();
+    if let Some(object) = response.as_object_mut() {
+        object.remove("cid");
+        if command == "Status" {
+            object.remove("result");
+        };
+        if let Some(error) = object.get_mut("error").and_then(Value::as_object_mut) {
+            error.remove("message");
+        };
+    };
+    response
+}
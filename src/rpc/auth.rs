@@ -0,0 +1,50 @@
+//! 连接认证
+//!
+//! 本机任意进程都可以连接到服务端端口, 因此每个连接的第一条消息必须携带令牌,
+//! 否则服务端拒绝该连接。令牌在启动时随机生成并随端口号一同输出, 也可从文件读取。
+
+use std::io;
+use std::path::Path;
+
+/// 随机令牌字节数, 输出为两倍长度的十六进制字符串
+const TOKEN_BYTES: usize = 16;
+
+#[derive(Clone)]
+pub(crate) struct AuthToken {
+    token: String,
+}
+impl AuthToken {
+    /// 使用操作系统随机数生成令牌
+    pub(crate) fn generate() -> io::Result<AuthToken> {
+        let mut bytes = [0u8; TOKEN_BYTES];
+        getrandom::fill(&mut bytes).map_err(|e| io::Error::other(e.to_string()))?;
+        let token = bytes.iter().map(|b| format!("{b:02x}")).collect();
+        Ok(AuthToken { token })
+    }
+
+    /// 从文件读取令牌, 忽略首尾空白
+    pub(crate) fn from_file(path: &Path) -> io::Result<AuthToken> {
+        let token = std::fs::read_to_string(path)?.trim().to_string();
+        if token.is_empty() {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "Token file is empty"));
+        };
+        Ok(AuthToken { token })
+    }
+
+    pub(crate) fn as_str(&self) -> &str {
+        &self.token
+    }
+
+    /// 校验客户端提供的令牌, 比较耗时与令牌内容无关
+    pub(crate) fn verify(&self, token: Option<&str>) -> bool {
+        let token = match token {
+            Some(t) => t.as_bytes(),
+            None => return false,
+        };
+        let expected = self.token.as_bytes();
+        if token.len() != expected.len() {
+            return false;
+        };
+        token.iter().zip(expected).fold(0u8, |acc, (a, b)| acc | (a ^ b)) == 0
+    }
+}
@@ -0,0 +1,39 @@
+//! 消息编码
+//!
+//! 默认使用 json 编码, 客户端可通过 Handshake 请求协商二进制编码,
+//! 二进制编码复用请求与响应的 serde 定义, 避免大段代码文本的转义开销。
+//!
+//! 握手响应仍使用握手请求的编码, 之后双方的消息均使用协商后的编码。
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, Eq, PartialEq, Default)]
+pub(crate) enum Encoding {
+    #[default]
+    Json,
+    MessagePack,
+    Cbor,
+}
+impl Encoding {
+    pub(crate) fn encode<T: Serialize>(&self, value: &T) -> Vec<u8> {
+        match self {
+            Encoding::Json => serde_json::to_vec(value).unwrap(),
+            // 结构体按字段名编码为 map, 与 json 结构保持一致
+            Encoding::MessagePack => rmp_serde::to_vec_named(value).unwrap(),
+            Encoding::Cbor => {
+                let mut buffer = Vec::new();
+                ciborium::into_writer(value, &mut buffer).unwrap();
+                buffer
+            },
+        }
+    }
+
+    pub(crate) fn decode<T: DeserializeOwned>(&self, message: &[u8]) -> Result<T, String> {
+        match self {
+            Encoding::Json => serde_json::from_slice(message).map_err(|e| e.to_string()),
+            Encoding::MessagePack => rmp_serde::from_slice(message).map_err(|e| e.to_string()),
+            Encoding::Cbor => ciborium::from_reader(message).map_err(|e| e.to_string()),
+        }
+    }
+}
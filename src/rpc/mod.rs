@@ -1,7 +1,13 @@
+mod auth;
+mod codec;
 mod request;
 mod response;
 mod socket;
+mod trace;
 
+pub(super) use auth::*;
+pub(super) use codec::*;
 pub(super) use socket::*;
 pub(super) use response::*;
 pub(super) use request::*;
+pub(super) use trace::*;
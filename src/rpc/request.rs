@@ -1,49 +1,111 @@
 //! #### 客户端请求样式
 //! ```json
 //! {
-//!     cid: u16, // 客户端ID，用于标识客户端身份 值为0时自动分配cid
+//!     // 认证令牌, 每个连接的第一条消息必须携带, 之后的消息可省略
+//!     token: Null / String,
 //!
-//!     // Exit 时服务端将会结束自身的运行，服务端一段时间无客户端连接也会自动退出
+//!     // 可选 请求ID, 服务端在对应响应中原样返回
+//!     // 客户端可连续发送多个请求, 响应可能乱序到达, 需按 id 匹配
+//!     id: Null / u64,
+//!
+//!     // 客户端ID，用于标识客户端会话 值为0或未知时自动分配新的cid
+//!     // 断线重连时携带上次分配的cid 即可恢复会话的文档缓存与输入法状态
+//!     cid: u16,
+//!
+//!     // Exit 时服务端将会结束自身的运行（守护进程模式下仅最后一个连接生效），服务端一段时间无客户端连接也会自动退出
 //!     // Switch 时 将会执行语法分析 与输入法自动切换
 //!     // Analyze 时 仅执行 语法分析
+//!     // AnalyzeBatch 时 对同一文档的多个光标位置执行语法分析, 只解析一次
 //!     // MethodOnly 时 仅执行输入法切换
-//!     command: Exit, Switcher, Analyze, MethodOnly
+//!     // Subscribe 时 订阅输入法变化通知, Unsubscribe 取消订阅
+//!     // Cancel 时 取消尚在排队中的 Switch / MethodOnly 请求
+//!     // Handshake 时 协商后续消息的编码, 只能作为连接的第一条消息
+//!     // Status 时 返回服务端运行状态 与 统计信息, 连接尚未绑定会话且 cid 为 0 时不分配会话
+//!     // SetLogLevel 时 修改日志级别
+//!     command: Exit, Switcher, Analyze, AnalyzeBatch, MethodOnly, Subscribe, Unsubscribe, Cancel, Handshake, Status, SetLogLevel
 //!
 //!     /// 按照命令类型区分 Analyze 参数
 //!     params: {
+//!         document: Null / String,  // 可选 文档标识(如文件路径), 用于会话内语法树缓存
 //!         code: String,  // 原始代码
 //!
 //!         // 代码类型,注意首字母大写
-//!         // 名称应与 crate::core::SupportLanguage 枚举中保持一致
+//!         // 名称应与 crate::core::SupportLanguage 枚举 或 运行时加载的语法名称保持一致
 //!         language: String,
 //!
-//!         // 光标位置 UTF-16 字符位置, 0基
+//!         // 光标位置 0基, column 单位由握手协商的 position_encoding 决定, 缺省为行内 utf-8 字节偏移量
 //!         cursor: {
 //!             row: usize,
 //!             column: usize
+//!         },
+//!
+//!         // 可选 可见区域的行范围（0基, 包含两端）, 提供时响应中附带该区域内的所有上下文区间
+//!         visible: Null / {
+//!             start_row: usize,
+//!             end_row: usize
 //!         }
 //!     },
 //!
+//!     /// AnalyzeBatch 参数
+//!     /// 适用于多光标编辑 或 预先计算可见行的语法上下文
+//!     params: {
+//!         document: Null / String,  // 可选 文档标识(如文件路径), 用于会话内语法树缓存
+//!         code: String,  // 原始代码
+//!         language: String,  // 代码类型, 同 Analyze
+//!
+//!         // 光标位置列表, 格式同 Analyze 的 cursor
+//!         cursors: [
+//!             { row: usize, column: usize },
+//!         ]
+//!     },
+//!
 //!     /// MethodOnly 参数
 //!     params: {
 //!         mode: Native / English,  // 目标输入法, 首字母大写
 //!     },
 //!
 //!     /// Switch 参数
+//!     /// 同一会话同一文档的多个 Switch 请求排队时, 仅执行最新的一个, 其余以 Cancelled 错误响应;
+//!     /// 未提供 document 的请求无法区分文档, 不会相互取代
 //!     params: {
+//!         document: Null / String,  // 可选 文档标识(如文件路径), 用于会话内语法树缓存
 //!         code: String,  // 原始代码
 //!
 //!         // 代码类型,注意首字母大写
-//!         // 名称应与 crate::core::SupportLanguage 枚举中保持一致
+//!         // 名称应与 crate::core::SupportLanguage 枚举 或 运行时加载的语法名称保持一致
 //!         language: String,
 //!
-//!         // 光标位置 UTF-16 字符位置, 0基
+//!         // 光标位置 0基, column 单位由握手协商的 position_encoding 决定, 缺省为行内 utf-8 字节偏移量
 //!         cursor: {
 //!             row: usize,
 //!             column: usize
 //!         }
 //!     },
 //!
+//!     /// Cancel 参数
+//!     params: {
+//!         id: u64,  // 需要取消的请求 id
+//!     },
+//!
+//!     /// Handshake 参数
+//!     params: {
+//!         encoding: Json / MessagePack / Cbor,  // 后续消息使用的编码, 缺省为 Json
+//!         // 光标列位置的单位: utf-8 字节 / utf-16 码元 / Unicode 码点, 缺省为 Utf8
+//!         // 请求中的 cursor 与 响应中的区间位置均使用该单位, 服务端按行文本转换
+//!         position_encoding: Utf8 / Utf16 / Utf32,
+//!     },
+//!
+//!     /// SetLogLevel 参数
+//!     params: {
+//!         // 日志级别规则, 如 "debug" 或 "rpc=debug,switch=trace", 在当前规则上覆盖
+//!         filter: String,
+//!     },
+//!
+//!     /// Subscribe / Unsubscribe / Status 参数
+//!     params: {
+//!         // 无参数, 空的 一对花括号
+//!     },
+//!
 //!     /// Exit 参数
 //!     params: {
 //!         // 无参数, 空的 一对花括号
@@ -51,22 +113,50 @@
 //! }
 //! ```
 
-use crate::core::Cursor;
+use super::codec::Encoding;
+use crate::core::{Cursor, PositionEncoding};
 use serde::{Deserialize, Serialize};
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
 pub(crate) enum CommandMode {
     Analyze,
+    AnalyzeBatch,
     MethodOnly,
     Switch,
+    Subscribe,
+    Unsubscribe,
+    Cancel,
+    Handshake,
+    Status,
+    SetLogLevel,
     Exit,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
 pub(crate) struct AnalyzeParams {
+    #[serde(default)]
+    pub(crate) document: Option<String>,
     pub(crate) code: String,
     pub(crate) language: String,
     pub(crate) cursor: Cursor,
+    #[serde(default)]
+    pub(crate) visible: Option<VisibleRows>,
+}
+
+/// 可见区域的行范围, 0基 包含两端
+#[derive(Serialize, Deserialize, Debug, Copy, Clone)]
+pub(crate) struct VisibleRows {
+    pub(crate) start_row: usize,
+    pub(crate) end_row: usize,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub(crate) struct AnalyzeBatchParams {
+    #[serde(default)]
+    pub(crate) document: Option<String>,
+    pub(crate) code: String,
+    pub(crate) language: String,
+    pub(crate) cursors: Vec<Cursor>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -76,21 +166,46 @@ pub(crate) struct MethodOnlyParams {
 
 #[derive(Serialize, Deserialize, Debug)]
 pub(crate) struct SwitchParams {
+    #[serde(default)]
+    pub(crate) document: Option<String>,
     pub(crate) code: String,
     pub(crate) language: String,
     pub(crate) cursor: Cursor,
 }
 
+#[derive(Serialize, Deserialize, Debug)]
+pub(crate) struct HandshakeParams {
+    #[serde(default)]
+    pub(crate) encoding: Encoding,
+    #[serde(default)]
+    pub(crate) position_encoding: PositionEncoding,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub(crate) struct CancelParams {
+    pub(crate) id: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub(crate) struct LogLevelParams {
+    pub(crate) filter: String,
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 #[serde(transparent)]
 pub(crate) struct CommandParams {
     pub(crate) params: serde_json::Value,
 }
+#[allow(clippy::wrong_self_convention)]  // 参数按值转换, 避免复制整段代码文本
 impl CommandParams {
     pub(crate) fn to_analyze_params(self) -> Result<AnalyzeParams, serde_json::Error> {
         serde_json::from_value(self.params)
     }
 
+    pub(crate) fn to_analyze_batch_params(self) -> Result<AnalyzeBatchParams, serde_json::Error> {
+        serde_json::from_value(self.params)
+    }
+
     pub(crate) fn to_method_only_params(self) -> Result<MethodOnlyParams, serde_json::Error> {
         serde_json::from_value(self.params)
     }
@@ -98,20 +213,27 @@ impl CommandParams {
     pub(crate) fn to_switch_params(self) -> Result<SwitchParams, serde_json::Error> {
         serde_json::from_value(self.params)
     }
+
+    pub(crate) fn to_cancel_params(self) -> Result<CancelParams, serde_json::Error> {
+        serde_json::from_value(self.params)
+    }
+
+    pub(crate) fn to_handshake_params(self) -> Result<HandshakeParams, serde_json::Error> {
+        serde_json::from_value(self.params)
+    }
+
+    pub(crate) fn to_log_level_params(self) -> Result<LogLevelParams, serde_json::Error> {
+        serde_json::from_value(self.params)
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug)]
 pub(crate) struct ClientRequest {
+    #[serde(default)]
+    pub(crate) token: Option<String>,
+    #[serde(default)]
+    pub(crate) id: Option<u64>,
     pub(crate) cid: u16,
     pub(crate) command: CommandMode,
     pub(crate) params: CommandParams,
 }
-impl ClientRequest {
-    pub(crate) fn from_json_message(json_string: String) -> Result<ClientRequest, String> {
-        // 使用 serde_json::from_str 函数
-        match serde_json::from_str(&json_string) {
-            Ok(request) => Ok(request),
-            Err(json_error) => Err(json_error.to_string()),
-        }
-    }
-}
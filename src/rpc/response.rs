@@ -4,14 +4,45 @@
 //!
 //! ```json
 //! {
+//!     id: Null / u64,           // 与请求 id 一致, 请求未提供 id 时为 Null
 //!     cid: u16,                 // 服务端确认的客户端 ID（与请求一致）
 //!     success: bool,            // 请求是否成功（true 表示操作成功）
-//!     error: Null / String      // 当 success = false 时, 此字段包含轻轻执行错误原因 成功则为 Null
+//!     error: Null / {}          // 当 success = false 时, 此字段包含错误对象 成功则为 Null
 //!     result: Null / {}         // 当 success = true 时，此字段包含执行结果 不成功则为 Null
 //!
+//!     /// 错误对象
+//!     error: {
+//!         // 稳定的机器可读错误码, 客户端应据此区分处理而非匹配 message
+//!         code: ParseError / InvalidParams / UnsupportedLanguage / BackendUnavailable / SwitchFailed / QueryFailed / Cancelled / FrameTooLarge / Unauthorized,
+//!         message: String,      // 人类可读的错误描述
+//!         data: Null / {}       // 可选的附加数据, 如 SwitchFailed 时附带语法分析结果
+//!     }
+//!
 //!     /// Analyze 请求结果
 //!     result: {
-//!         grammar: Comment / Code
+//!         // Prose 为 Markdown 等文档中的正文, 与 Comment 一样对应母语输入法
+//!         grammar: Comment / Code / Prose,
+//!
+//!         // 光标所在的上下文区间, 光标在区间内移动 且 文档未被编辑时 grammar 不变, 客户端可据此跳过重复请求
+//!         range: {
+//!             grammar: Comment / Code / Prose,
+//!             start: { row: usize, column: usize },  // 格式 与 column 单位同请求中的 cursor
+//!             end: { row: usize, column: usize },
+//!             start_byte: usize,                    // 文档内的 utf-8 字节偏移量, 左闭右开, 不受 position_encoding 影响
+//!             end_byte: usize,
+//!             // 光标恰好位于 start / end 时是否属于该区间, 与 grammar 的判断规则一致
+//!             // 如注释之后同一行仍有代码时, 注释区间的 end 不属于该注释, 而属于其后的代码区间
+//!             start_inclusive: bool,
+//!             end_inclusive: bool,
+//!         },
+//!
+//!         // 请求提供 visible 时为可见区域内的所有上下文区间, 按位置排序, 相邻区间的 grammar 不同; 否则为 Null
+//!         ranges: Null / [range]
+//!     }
+//!
+//!     /// AnalyzeBatch 请求结果, 与请求中的 cursors 一一对应
+//!     result: {
+//!         grammars: [Comment / Code / Prose]
 //!     }
 //!
 //!     /// ModeOnly 请求结果
@@ -21,30 +52,114 @@
 //!
 //!     /// Switch 请求结果
 //!     result: {
-//!         grammar : Comment / Code,
+//!         grammar : Comment / Code / Prose,
 //!         method: Native / English,
 //!     }
 //!
+//!     /// Subscribe 请求结果, 当前输入法状态
+//!     result: {
+//!         method: Native / English
+//!     }
+//!
+//!     /// Unsubscribe 无请求结果
+//!
+//!     /// Cancel 请求结果, 目标请求仍在排队并被取消时为 true
+//!     /// 被取消的请求自身以 Cancelled 错误响应
+//!     result: {
+//!         cancelled: bool
+//!     }
+//!
+//!     /// Handshake 请求结果, 握手响应仍使用原编码, 之后的消息使用协商后的编码
+//!     result: {
+//!         version: String,                      // 服务端版本
+//!         encoding: Json / MessagePack / Cbor,  // 协商后的编码
+//!         position_encoding: Utf8 / Utf16 / Utf32,  // 协商后的光标列位置单位
+//!     }
+//!
+//!     /// Status 请求结果, 服务端运行状态 与 统计信息
+//!     result: {
+//!         version: String,
+//!         uptime_secs: u64,
+//!         clients: usize,                // 当前连接数
+//!         sessions: usize,               // 当前会话数
+//!         languages: [String],           // 已加载语法的语言
+//!         backend: {
+//!             name: Null / String,       // 输入法后端, 如 fcitx5, 初始化失败时为 Null
+//!             healthy: bool,             // 后端可用 且 最近一次调用成功
+//!             last_error: Null / {
+//!                 message: String,
+//!                 uptime_secs: u64,      // 发生错误时的运行时间
+//!             },
+//!         },
+//!         requests: { Analyze: u64, Switch: u64, ... },  // 各命令的请求次数
+//!         // 耗时统计（微秒）: parse 为语法树构建, query 为注释节点查询, switch 为输入法后端切换
+//!         // 百分位数 与 max 基于最近 1024 个样本
+//!         latency: {
+//!             parse / query / switch: {
+//!                 count: u64, avg_us: u64, p50_us: u64, p95_us: u64, p99_us: u64, max_us: u64,
+//!             },
+//!         },
+//!     }
+//!
+//!     /// SetLogLevel 请求结果, 修改后完整的日志级别规则
+//!     result: {
+//!         filter: String,  // 如 "info,rpc=debug"
+//!     }
+//!
 //!     /// Exit 无请求结果, 服务器将断开网络连接之后结束自身
 //! }
 //! ```
+//!
+//! #### 服务端通知样式（Notification）
+//!
+//! 订阅后, 输入法在服务端控制之外发生变化时（如用户手动切换）服务端主动推送通知,
+//! 与响应使用相同的消息帧, 客户端通过是否存在 notification 字段区分通知与响应
+//!
+//! ```json
+//! {
+//!     cid: u16,
+//!     notification: MethodChanged,
+//!
+//!     /// MethodChanged 通知内容
+//!     result: {
+//!         method: Native / English
+//!     }
+//! }
+//! ```
 
 use serde::{Deserialize, Serialize};
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, Eq, PartialEq)]
 pub(crate) enum GrammarMode {
     Code,
     Comment,
+    /// Markdown 等文档中的正文
+    Prose,
 }
-impl GrammarMode {
-    pub(crate) fn from_bool(in_comment: bool) -> GrammarMode {
-        if in_comment { GrammarMode::Comment } else { GrammarMode::Code }
-    }
+
+/// 文档中的一段 代码、注释 或 正文 上下文
+#[derive(Serialize, Deserialize, Debug, Copy, Clone)]
+pub(crate) struct ContextRange {
+    pub(crate) grammar: GrammarMode,
+    pub(crate) start: crate::core::Cursor,
+    pub(crate) end: crate::core::Cursor,
+    pub(crate) start_byte: usize,
+    pub(crate) end_byte: usize,
+    pub(crate) start_inclusive: bool,
+    pub(crate) end_inclusive: bool,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
 pub(crate) struct AnalyzeResult {
     pub(crate) grammar: GrammarMode,
+    pub(crate) range: ContextRange,
+    #[serde(default)]
+    pub(crate) ranges: Option<Vec<ContextRange>>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub(crate) struct AnalyzeBatchResult {
+    pub(crate) grammars: Vec<GrammarMode>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -57,6 +172,66 @@ pub(crate) struct SwitchResult {
     pub(crate) grammar: GrammarMode,
     pub(crate) method: crate::core::InputMethodMode,
 }
+#[derive(Serialize, Deserialize, Debug)]
+pub(crate) struct HandshakeResult {
+    pub(crate) version: String,
+    pub(crate) encoding: super::codec::Encoding,
+    pub(crate) position_encoding: crate::core::PositionEncoding,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub(crate) struct CancelResult {
+    pub(crate) cancelled: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub(crate) struct LogLevelResult {
+    pub(crate) filter: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub(crate) struct BackendError {
+    pub(crate) message: String,
+    pub(crate) uptime_secs: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub(crate) struct BackendStatus {
+    pub(crate) name: Option<String>,
+    pub(crate) healthy: bool,
+    pub(crate) last_error: Option<BackendError>,
+}
+
+/// 耗时统计, 单位为微秒
+#[derive(Serialize, Deserialize, Debug, Default, Eq, PartialEq)]
+pub(crate) struct LatencySummary {
+    pub(crate) count: u64,
+    pub(crate) avg_us: u64,
+    pub(crate) p50_us: u64,
+    pub(crate) p95_us: u64,
+    pub(crate) p99_us: u64,
+    pub(crate) max_us: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub(crate) struct LatencyReport {
+    pub(crate) parse: LatencySummary,
+    pub(crate) query: LatencySummary,
+    pub(crate) switch: LatencySummary,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub(crate) struct StatusResult {
+    pub(crate) version: String,
+    pub(crate) uptime_secs: u64,
+    pub(crate) clients: usize,
+    pub(crate) sessions: usize,
+    pub(crate) languages: Vec<String>,
+    pub(crate) backend: BackendStatus,
+    pub(crate) requests: std::collections::BTreeMap<super::request::CommandMode, u64>,
+    pub(crate) latency: LatencyReport,
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 #[serde(transparent)]
 pub(crate) struct CommandResult {
@@ -67,6 +242,10 @@ impl CommandResult {
         CommandResult { result: serde_json::to_value(&result).unwrap() }
     }
 
+    pub(crate) fn from_analyze_batch_result(result: AnalyzeBatchResult) -> CommandResult {
+        CommandResult { result: serde_json::to_value(&result).unwrap() }
+    }
+
     pub(crate) fn from_method_only_result(result: MethodOnlyResult) -> CommandResult {
         CommandResult { result: serde_json::to_value(&result).unwrap() }
     }
@@ -74,21 +253,112 @@ impl CommandResult {
     pub(crate) fn from_switch_result(result: SwitchResult) -> CommandResult {
         CommandResult { result: serde_json::to_value(&result).unwrap() }
     }
+
+    pub(crate) fn from_cancel_result(result: CancelResult) -> CommandResult {
+        CommandResult { result: serde_json::to_value(&result).unwrap() }
+    }
+
+    pub(crate) fn from_handshake_result(result: HandshakeResult) -> CommandResult {
+        CommandResult { result: serde_json::to_value(&result).unwrap() }
+    }
+
+    pub(crate) fn from_status_result(result: StatusResult) -> CommandResult {
+        CommandResult { result: serde_json::to_value(&result).unwrap() }
+    }
+
+    pub(crate) fn from_log_level_result(result: LogLevelResult) -> CommandResult {
+        CommandResult { result: serde_json::to_value(&result).unwrap() }
+    }
+}
+
+/// 稳定的机器可读错误码
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, Eq, PartialEq)]
+pub(crate) enum ErrorCode {
+    /// 请求消息无法解析为合法的 ClientRequest
+    ParseError,
+    /// 请求参数缺失或格式错误
+    InvalidParams,
+    /// 请求的语言不在 SupportLanguage 中
+    UnsupportedLanguage,
+    /// 输入法后端初始化失败或不可用
+    BackendUnavailable,
+    /// 输入法切换失败
+    SwitchFailed,
+    /// 输入法状态查询失败
+    QueryFailed,
+    /// 请求在执行前被 Cancel 取消, 或被同一文档更新的 Switch 请求取代
+    Cancelled,
+    /// 消息帧长度超过服务端上限, 服务端随后断开连接
+    FrameTooLarge,
+    /// 连接的第一条消息未携带正确的令牌, 服务端随后断开连接
+    Unauthorized,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub(crate) struct ResponseError {
+    pub(crate) code: ErrorCode,
+    pub(crate) message: String,
+    pub(crate) data: Option<serde_json::Value>,
+}
+impl ResponseError {
+    pub(crate) fn new(code: ErrorCode, message: impl ToString) -> ResponseError {
+        ResponseError { code, message: message.to_string(), data: None }
+    }
+
+    pub(crate) fn with_data(mut self, data: serde_json::Value) -> ResponseError {
+        self.data = Some(data);
+        self
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug)]
 pub(crate) struct ClientResponse {
+    id: Option<u64>,
     cid: u16,
     success: bool,
-    error: Option<String>,
+    error: Option<ResponseError>,
     result: Option<CommandResult>,
 }
 impl ClientResponse {
-    pub(crate) fn new(cid: u16, success: bool, error: Option<String>, result: Option<CommandResult>) -> ClientResponse {
-        ClientResponse { cid, success, error, result }
+    pub(crate) fn new(cid: u16, success: bool, error: Option<ResponseError>, result: Option<CommandResult>) -> ClientResponse {
+        ClientResponse { id: None, cid, success, error, result }
+    }
+
+    /// 回填请求 id
+    pub(crate) fn with_id(mut self, id: Option<u64>) -> ClientResponse {
+        self.id = id;
+        self
     }
 
-    pub(crate) fn to_json_message(&self) -> String {
-        serde_json::to_string(&self).unwrap()
+    pub(crate) fn success(cid: u16, result: CommandResult) -> ClientResponse {
+        ClientResponse::new(cid, true, None, Some(result))
+    }
+
+    pub(crate) fn failure(cid: u16, error: ResponseError) -> ClientResponse {
+        ClientResponse::new(cid, false, Some(error), None)
+    }
+
+    pub(crate) fn is_success(&self) -> bool {
+        self.success
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, Eq, PartialEq)]
+pub(crate) enum NotificationEvent {
+    /// 输入法状态在服务端控制之外发生变化
+    MethodChanged,
+}
+
+/// 服务端主动推送的通知
+#[derive(Serialize, Deserialize, Debug)]
+pub(crate) struct ServerNotification {
+    cid: u16,
+    notification: NotificationEvent,
+    result: CommandResult,
+}
+impl ServerNotification {
+    pub(crate) fn method_changed(cid: u16, method: crate::core::InputMethodMode) -> ServerNotification {
+        let result = CommandResult::from_method_only_result(MethodOnlyResult { method });
+        ServerNotification { cid, notification: NotificationEvent::MethodChanged, result }
     }
 }
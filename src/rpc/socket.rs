@@ -1,12 +1,21 @@
-//! 网络通信模块 服务端与客户端一一对应 使用同步装后台完成，不引入异步
+//! 网络通信模块 每个客户端连接在独立线程中同步处理，不引入异步
 //!
 //! 解决 tcp 协议粘包问题
 //! 对收发消息格式做出规定:
-//!      [u64 message size][json message]
+//!      [u64 message size][message]
+//!
+//! message size 为大端序, 超过配置上限的消息帧将被拒绝
+//! message 默认为 json 编码, 可在握手时协商为二进制编码
 //!
 
+use super::codec::Encoding;
+use super::trace::Tracer;
+use serde::Serialize;
+use std::fmt::Display;
 use std::io::{self, Read, Write};
-use std::net::{TcpListener, TcpStream};
+use std::net::{Shutdown, TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
 /// 让系统分配可用端口 并返回端口 与 socket
 pub(crate) fn init_socket() -> io::Result<(u16, TcpListener)> {
@@ -23,29 +32,106 @@ pub(crate) fn accept_connect(listener: &TcpListener) -> io::Result<TcpStream> {
     Ok(client_socket.0)
 }
 
-/// 接受客户端消息并转换为 utf-8 字符串
-pub(crate) fn recv_message(client: &mut TcpStream) -> io::Result<String> {
-    // 读取 消息长度
+/// 接收消息帧失败的原因
+#[derive(Debug)]
+pub(crate) enum FrameError {
+    /// 连接断开、读取失败或读取超时, 连接不可继续使用
+    Io(io::Error),
+    /// 声明的消息长度超过上限, 帧边界不可信, 连接不可继续使用
+    TooLarge { size: u64, limit: usize },
+}
+impl From<io::Error> for FrameError {
+    fn from(e: io::Error) -> FrameError {
+        FrameError::Io(e)
+    }
+}
+impl Display for FrameError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            FrameError::Io(e) => write!(f, "{e}"),
+            FrameError::TooLarge { size, limit } => write!(f, "Frame size {size} exceeds limit {limit}"),
+        }
+    }
+}
+
+/// 接受客户端消息, 返回未解码的消息内容
+///
+/// 等待消息到达的超时为 wait, None 时不设超时, 已认证的客户端空闲属于正常情况;
+/// 消息开始到达后, 剩余部分需在 read_timeout 内读取完毕, 避免不完整的消息帧永久阻塞连接
+pub(crate) fn recv_message(
+    client: &mut TcpStream, max_size: usize, wait: Option<Duration>, read_timeout: Duration,
+) -> Result<Vec<u8>, FrameError> {
+    client.set_read_timeout(wait)?;
+    let mut probe = [0u8; 1];
+    if client.peek(&mut probe)? == 0 {
+        return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "client closed").into());
+    };
+    client.set_read_timeout(Some(read_timeout))?;
+    // 读取 消息长度, 先校验再分配缓冲区
     let mut len_buf = [0u8; 8];
     client.read_exact(&mut len_buf)?;
-    let len = u64::from_be_bytes(len_buf) as usize;
+    let size = u64::from_be_bytes(len_buf);
+    if size > max_size as u64 {
+        return Err(FrameError::TooLarge { size, limit: max_size });
+    };
     // 读取消息
-    let mut buffer = vec![0u8; len];
+    let mut buffer = vec![0u8; size as usize];
     client.read_exact(&mut buffer)?;
-    match std::str::from_utf8(&buffer) {
-        Ok(s) => Ok(s.to_string()),
-        Err(e) => Err(io::Error::new(io::ErrorKind::InvalidData, e)),
-    }
+    Ok(buffer)
 }
 
 /// 向客户端发送消息
 /// TCP底层有重发机制，这里不再实现
-pub(crate) fn send_message(client: &mut TcpStream, message: String) -> io::Result<()> {
+pub(crate) fn send_message(client: &mut TcpStream, message: &[u8]) -> io::Result<()> {
     // 使用 write_all 确保将整个 buffer 发送出去
     let len = (message.len() as u64).to_be_bytes();
     let mut buffer = Vec::with_capacity(8 + message.len());
     buffer.extend_from_slice(&len);
-    buffer.extend(message.as_bytes());
+    buffer.extend(message);
     client.write_all(&buffer)?;
     Ok(())
 }
+
+/// 可跨线程共享的消息发送端
+/// 多个线程同时向同一客户端发送消息时, 保证消息帧不会交错
+#[derive(Clone)]
+pub(crate) struct MessageWriter {
+    inner: Arc<Mutex<WriterInner>>,
+}
+struct WriterInner {
+    stream: TcpStream,
+    encoding: Encoding,
+    /// 启用 trace 时记录发出的消息 与 所属连接编号
+    trace: Option<(Tracer, u64)>,
+}
+impl MessageWriter {
+    pub(crate) fn new(client: &TcpStream) -> io::Result<MessageWriter> {
+        let inner = WriterInner { stream: client.try_clone()?, encoding: Encoding::default(), trace: None };
+        Ok(MessageWriter { inner: Arc::new(Mutex::new(inner)) })
+    }
+
+    /// 记录此后发出的所有消息
+    pub(crate) fn set_trace(&self, tracer: Tracer, conn: u64) {
+        self.inner.lock().unwrap().trace = Some((tracer, conn));
+    }
+
+    /// 切换后续消息使用的编码
+    pub(crate) fn set_encoding(&self, encoding: Encoding) {
+        self.inner.lock().unwrap().encoding = encoding;
+    }
+
+    /// 按当前编码编码并发送消息
+    pub(crate) fn send<T: Serialize>(&self, message: &T) -> io::Result<()> {
+        let mut inner = self.inner.lock().unwrap();
+        if let Some((tracer, conn)) = &inner.trace {
+            tracer.record_outgoing(*conn, message);
+        };
+        let message = inner.encoding.encode(message);
+        let result = send_message(&mut inner.stream, &message);
+        if result.is_err() {
+            // 写入失败 或 超时时消息帧可能不完整, 关闭连接, 连接线程随之结束
+            let _ = inner.stream.shutdown(Shutdown::Both);
+        };
+        result
+    }
+}
@@ -0,0 +1,102 @@
+//! 请求 与 响应的 trace 记录
+//!
+//! 启用后将每个连接收到的请求、发出的响应 与 通知按时间顺序写入 JSONL 文件, 每行一条记录:
+//! ```json
+//! {"ts_ms": 1760000000000, "conn": 1, "kind": "Request", "redacted": false, "message": {...}}
+//! ```
+//! - conn: 服务端内部的连接编号, 同一连接的记录按顺序回放
+//! - kind: Request / Response / Notification
+//! - message: 原始请求或响应, 请求中的认证令牌不会被记录
+//! - redacted: 启用脱敏时请求中的代码原文替换为长度, 文档标识一并隐去, 此类请求无法回放语法分析
+//!
+//! 记录中可能包含代码原文, 文件仅允许当前用户读写
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fs::{File, OpenOptions};
+use std::io::{self, LineWriter, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, Eq, PartialEq)]
+pub(crate) enum TraceKind {
+    Request,
+    Response,
+    Notification,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub(crate) struct TraceRecord {
+    pub(crate) ts_ms: u64,
+    pub(crate) conn: u64,
+    pub(crate) kind: TraceKind,
+    #[serde(default)]
+    pub(crate) redacted: bool,
+    pub(crate) message: Value,
+}
+
+/// 可跨线程共享的 trace 写入端
+#[derive(Clone)]
+pub(crate) struct Tracer {
+    file: Arc<Mutex<LineWriter<File>>>,
+    redact: bool,
+}
+impl Tracer {
+    pub(crate) fn create(path: &Path, redact: bool) -> io::Result<Tracer> {
+        let mut options = OpenOptions::new();
+        options.create(true).write(true).truncate(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        let file = options.open(path)?;
+        // mode 仅对新建文件生效, 已存在的文件需收紧权限
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            file.set_permissions(std::fs::Permissions::from_mode(0o600))?;
+        }
+        Ok(Tracer { file: Arc::new(Mutex::new(LineWriter::new(file))), redact })
+    }
+
+    /// 记录收到的请求, 去除令牌 并按需脱敏代码 与 文档标识
+    pub(crate) fn record_request<T: Serialize>(&self, conn: u64, request: &T) {
+        let mut message = serde_json::to_value(request).unwrap_or(Value::Null);
+        if let Some(object) = message.as_object_mut() {
+            object.remove("token");
+        };
+        let mut redacted = false;
+        if self.redact && let Some(code) = message.pointer_mut("/params/code") && code.is_string() {
+            *code = Value::String(format!("<redacted {} bytes>", code.as_str().unwrap_or_default().len()));
+            redacted = true;
+        };
+        if self.redact && let Some(document) = message.pointer_mut("/params/document") && document.is_string() {
+            *document = Value::String("<redacted>".to_string());
+            redacted = true;
+        };
+        self._write(TraceRecord { ts_ms: Tracer::_now(), conn, kind: TraceKind::Request, redacted, message });
+    }
+
+    /// 记录发出的响应 或 通知
+    pub(crate) fn record_outgoing<T: Serialize>(&self, conn: u64, message: &T) {
+        let message = serde_json::to_value(message).unwrap_or(Value::Null);
+        let kind = match message.get("notification") {
+            Some(_) => TraceKind::Notification,
+            None => TraceKind::Response,
+        };
+        self._write(TraceRecord { ts_ms: Tracer::_now(), conn, kind, redacted: false, message });
+    }
+
+    fn _write(&self, record: TraceRecord) {
+        let line = serde_json::to_string(&record).unwrap();
+        if let Err(e) = writeln!(self.file.lock().unwrap(), "{line}") {
+            log::warn!("Failed to write trace: {e}");
+        };
+    }
+
+    fn _now() -> u64 {
+        SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_millis() as u64)
+    }
+}
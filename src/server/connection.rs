@@ -0,0 +1,353 @@
+//! 单个客户端连接的请求处理
+//!
+//! 每个连接运行在独立线程中, 拥有各自的语法解析器,
+//! 会话 与 输入法工作线程由所有连接共享
+
+use super::stats::Metric;
+use super::worker::*;
+use super::Sever;
+use crate::core::{Cursor, InputMethodMode, PositionEncoding};
+use crate::logger;
+use crate::parser::{Context, ContextKind, NodesRange, Parser};
+use crate::rpc::*;
+
+use std::io;
+use std::net::TcpStream;
+use std::sync::Arc;
+use std::time::Instant;
+
+pub(super) struct Connection {
+    server: Arc<Sever>,
+    /// 连接编号, 用于 trace 记录
+    id: u64,
+    parser: Parser,
+    /// 握手协商的光标列位置单位
+    position: PositionEncoding,
+}
+impl Connection {
+    pub(super) fn new(server: Arc<Sever>, id: u64) -> Connection {
+        let parser = Parser::new(server.grammars.clone());
+        Connection { server, id, parser, position: PositionEncoding::default() }
+    }
+
+    pub(super) fn handle_client(&mut self, client: &mut TcpStream) -> io::Result<()> {
+        // 响应可能由工作线程写回, 客户端不再读取时写入超时, 避免阻塞其他客户端的输入法切换
+        client.set_write_timeout(Some(self.server.config.write_timeout))?;
+        let writer = MessageWriter::new(client)?;
+        if let Some(tracer) = &self.server.tracer {
+            writer.set_trace(tracer.clone(), self.id);
+        };
+        log::debug!(target: "rpc", "Client connected from {:?}", client.peer_addr());
+        // 连接绑定的会话 cid, 由该连接上第一个需要会话的请求确定
+        let mut bound: Option<u16> = None;
+        let result = self._serve(client, &writer, &mut bound);
+        match &result {
+            Ok(_) => log::debug!(target: "rpc", "Client {bound:?} requested exit"),
+            Err(e) => log::debug!(target: "rpc", "Client {bound:?} disconnected: {e}"),
+        };
+        if let Some(cid) = bound {
+            self.server.sessions.lock().unwrap().release(cid);
+            self.server.worker.submit(Task { cid, id: None, job: Job::Disconnect, writer });
+        };
+        result
+    }
+
+    fn _serve(&mut self, client: &mut TcpStream, writer: &MessageWriter, bound: &mut Option<u16>) -> io::Result<()> {
+        // 连接的第一条消息始终为 json 编码, 之后可通过握手切换
+        let mut encoding = Encoding::default();
+        let mut authenticated = false;
+        loop {
+            // 未认证的连接需在认证超时内发送第一条消息, 避免占用连接线程 并阻止空闲退出
+            let wait = (!authenticated).then_some(self.server.config.auth_timeout);
+            let message = match recv_message(client, self.server.config.max_frame_size, wait, self.server.config.read_timeout) {
+                Ok(message) => message,
+                Err(FrameError::Io(e)) if !authenticated && matches!(e.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut) => {
+                    log::warn!(target: "rpc", "Client sent no message within the auth timeout, closing connection");
+                    return Err(e);
+                },
+                Err(e @ FrameError::TooLarge { size, limit }) => {
+                    // 帧边界不可信, 告知客户端后断开连接, 服务端继续等待新连接
+                    log::warn!(target: "rpc", "{e}, closing connection");
+                    let data = serde_json::json!({ "size": size, "limit": limit });
+                    let error = ResponseError::new(ErrorCode::FrameTooLarge, &e).with_data(data);
+                    writer.send(&ClientResponse::failure(bound.unwrap_or(0), error))?;
+                    return Err(io::Error::new(io::ErrorKind::InvalidData, e.to_string()));
+                },
+                Err(FrameError::Io(e)) => return Err(e),
+            };
+            let req: ClientRequest = match encoding.decode(&message) {
+                Ok(req) => req,
+                Err(_) if !authenticated => return Connection::_reject(writer),
+                Err(err) => {
+                    // 帧边界完整, 丢弃该消息后连接可继续使用
+                    log::debug!(target: "rpc", "Failed to decode request: {err}");
+                    let response = ClientResponse::failure(
+                        bound.unwrap_or(0),
+                        ResponseError::new(ErrorCode::ParseError, format!("Failed to analysis request! {err}")),
+                    );
+                    writer.send(&response)?;
+                    continue;
+                }
+            };
+            // 连接的第一条消息必须携带正确的令牌
+            if !authenticated && !self.server.token.verify(req.token.as_deref()) {
+                return Connection::_reject(writer);
+            };
+            if let Some(tracer) = &self.server.tracer {
+                tracer.record_request(self.id, &req);
+            };
+            let id = req.id;
+            log::trace!(target: "rpc", "Request {:?} id {id:?} cid {}", req.command, req.cid);
+            let first = !authenticated;
+            authenticated = true;
+            let cid = match *bound {
+                Some(cid) => cid,
+                // cid 为 0 的 Status 请求不分配会话, 用于探测服务端是否存活
+                None if req.command == CommandMode::Status && req.cid == 0 => 0,
+                None => *bound.insert(self._bind_session(req.cid, writer)),
+            };
+            if bound.is_some() {
+                self.server.sessions.lock().unwrap().touch(cid);
+            };
+            self.server.stats.lock().unwrap().record_request(req.command);
+            // 语法分析在连接线程内直接响应, 输入法操作交由工作线程执行后响应
+            let response = match req.command {
+                CommandMode::Analyze => self._grammar_analysis(cid, req),
+                CommandMode::AnalyzeBatch => self._batch_analysis(cid, req),
+                CommandMode::Status => self._status(cid),
+                CommandMode::SetLogLevel => Connection::_set_log_level(cid, req),
+                CommandMode::Handshake => {
+                    let response = Connection::_handshake(cid, first, req);
+                    // 握手响应仍使用原编码, 之后的消息使用协商后的编码
+                    writer.send(&response.0.with_id(id))?;
+                    if let Some(negotiated) = response.1 {
+                        encoding = negotiated.encoding;
+                        writer.set_encoding(negotiated.encoding);
+                        self.position = negotiated.position_encoding;
+                    };
+                    continue;
+                },
+                CommandMode::Exit => return Ok(()),
+                _ => match self._switch_job(cid, req) {
+                    Ok(job) => {
+                        self.server.worker.submit(Task { cid, id, job, writer: writer.clone() });
+                        continue;
+                    },
+                    Err(response) => response,
+                },
+            };
+            writer.send(&response.with_id(id))?;
+        }
+    }
+
+    fn _reject(writer: &MessageWriter) -> io::Result<()> {
+        // 未通过认证, 告知客户端后断开连接
+        log::warn!(target: "rpc", "Rejected client with missing or invalid token");
+        let error = ResponseError::new(ErrorCode::Unauthorized, "Missing or invalid token");
+        writer.send(&ClientResponse::failure(0, error))?;
+        Err(io::Error::new(io::ErrorKind::PermissionDenied, "unauthorized client"))
+    }
+
+    fn _handshake(cid: u16, first: bool, req: ClientRequest) -> (ClientResponse, Option<HandshakeParams>) {
+        // 处理 Command::Handshake 请求, 只允许作为连接的第一条消息, 避免与排队中的响应编码不一致
+        if !first {
+            let error = ResponseError::new(ErrorCode::InvalidParams, "Handshake must be the first message");
+            return (ClientResponse::failure(cid, error), None);
+        };
+        match req.params.to_handshake_params() {
+            Ok(params) => {
+                let res = HandshakeResult {
+                    version: env!("CARGO_PKG_VERSION").to_string(),
+                    encoding: params.encoding,
+                    position_encoding: params.position_encoding,
+                };
+                (ClientResponse::success(cid, CommandResult::from_handshake_result(res)), Some(params))
+            },
+            Err(e) => (ClientResponse::failure(cid, ResponseError::new(ErrorCode::InvalidParams, e)), None),
+        }
+    }
+
+    fn _bind_session(&mut self, cid: u16, writer: &MessageWriter) -> u16 {
+        let (cid, resumed) = self.server.sessions.lock().unwrap().resolve(cid);
+        if resumed {
+            // 会话恢复时还原输入法快照
+            let method = self.server.sessions.lock().unwrap().get_mut(cid).and_then(|s| s.method);
+            if let Some(method) = method {
+                self.server.worker.submit(Task { cid, id: None, job: Job::Restore(method), writer: writer.clone() });
+            }
+        };
+        cid
+    }
+
+    fn _switch_job(&mut self, cid: u16, req: ClientRequest) -> Result<Job, ClientResponse> {
+        // 校验输入法操作请求参数, 转换为工作线程任务
+        let invalid = |e: serde_json::Error| ClientResponse::failure(cid, ResponseError::new(ErrorCode::InvalidParams, e));
+        match req.command {
+            CommandMode::MethodOnly => {
+                let params = req.params.to_method_only_params().map_err(invalid)?;
+                let mode = InputMethodMode::from_str(params.mode).map_err(invalid)?;
+                Ok(Job::MethodOnly(mode))
+            },
+            CommandMode::Switch => {
+                // 先完成语法分析, 再由工作线程根据结果切换输入法
+                let params = req.params.to_switch_params().map_err(invalid)?;
+                let document = params.document;
+                let grammar = self._grammar(cid, document.clone(), &params.language, &params.code, &params.cursor)
+                    .map_err(|e| ClientResponse::failure(cid, e))?;
+                Ok(Job::Switch { document, grammar })
+            },
+            CommandMode::Subscribe => Ok(Job::Subscribe),
+            CommandMode::Unsubscribe => Ok(Job::Unsubscribe),
+            CommandMode::Cancel => {
+                let params = req.params.to_cancel_params().map_err(invalid)?;
+                Ok(Job::Cancel(params.id))
+            },
+            CommandMode::Analyze | CommandMode::AnalyzeBatch | CommandMode::Handshake | CommandMode::Status
+            | CommandMode::SetLogLevel | CommandMode::Exit => {
+                unreachable!("handled by connection thread")
+            },
+        }
+    }
+
+    fn _grammar(
+        &mut self, cid: u16, document: Option<String>, language: &str, code: &str, cursor: &Cursor,
+    ) -> Result<GrammarMode, ResponseError> {
+        // 判断 cursor 是否在 comment 节点内部
+        let comments = self._comments(cid, document, language, code)?;
+        let cursor = self.position.to_byte_cursor(code, cursor);
+        Ok(grammar_mode(comments.kind_at(&cursor, code)))
+    }
+
+    fn _grammars(
+        &mut self, cid: u16, document: Option<String>, language: &str, code: &str, cursors: &[Cursor],
+    ) -> Result<Vec<GrammarMode>, ResponseError> {
+        // 逐个判断 cursor 是否在 comment 节点内部, 语法树构建 与 注释查询均只执行一次
+        let comments = self._comments(cid, document, language, code)?;
+        Ok(cursors.iter().map(|cursor| {
+            let cursor = self.position.to_byte_cursor(code, cursor);
+            grammar_mode(comments.kind_at(&cursor, code))
+        }).collect())
+    }
+
+    fn _comments(
+        &mut self, cid: u16, document: Option<String>, language: &str, code: &str,
+    ) -> Result<NodesRange, ResponseError> {
+        // 更新语法树 并查询所有 comment 节点
+        let language = match self.parser.language(language) {
+            Some(l) => l,
+            None => return Err(ResponseError::new(
+                ErrorCode::UnsupportedLanguage, format!("Unsupported language: {language}"),
+            )),
+        };
+        self.parser.add_language(language);
+        self.server.stats.lock().unwrap().record_language(language);
+        // 代码未变化时复用会话缓存的语法树
+        let document = document.unwrap_or_else(|| language.to_string());
+        // 仅在查找 与 写入缓存时持有会话锁, 解析期间不阻塞其他连接 与 工作线程
+        let cached = self.server.sessions.lock().unwrap()
+            .get_mut(cid).and_then(|s| s.cached_tree(&document, language, code));
+        match cached {
+            Some(tree) => self.parser.set_tree(tree),
+            None => {
+                let start = Instant::now();
+                self.parser.build_tree(language, code);
+                self.server.stats.lock().unwrap().record_latency(Metric::Parse, start.elapsed());
+                if let Some(tree) = self.parser.tree() {
+                    let mut sessions = self.server.sessions.lock().unwrap();
+                    if let Some(session) = sessions.get_mut(cid) {
+                        session.cache_tree(document, language, code, tree.clone());
+                    };
+                };
+            }
+        };
+        let start = Instant::now();
+        let comments = self.parser.get_comments(language, code);
+        self.server.stats.lock().unwrap().record_latency(Metric::Query, start.elapsed());
+        Ok(comments)
+    }
+
+    fn _context_range(&self, comments: &NodesRange, context: Context, code: &str) -> ContextRange {
+        // 区间位置转换为客户端使用的列单位
+        let (start, end) = (context.range.start_point, context.range.end_point);
+        let (start_inclusive, end_inclusive) = comments.includes(&context, code);
+        ContextRange {
+            grammar: grammar_mode(context.kind),
+            start: self.position.to_client_cursor(code, &Cursor { row: start.row, column: start.column }),
+            end: self.position.to_client_cursor(code, &Cursor { row: end.row, column: end.column }),
+            start_byte: context.range.start_byte,
+            end_byte: context.range.end_byte,
+            start_inclusive,
+            end_inclusive,
+        }
+    }
+
+    fn _set_log_level(cid: u16, req: ClientRequest) -> ClientResponse {
+        // Command::SetLogLevel 请求响应, 日志级别对所有连接生效
+        let invalid = |e: String| ClientResponse::failure(cid, ResponseError::new(ErrorCode::InvalidParams, e));
+        let params = match req.params.to_log_level_params() {
+            Ok(p) => p,
+            Err(e) => return invalid(e.to_string()),
+        };
+        match logger::set_filter(&params.filter) {
+            Ok(filter) => {
+                log::info!("Log level changed to {filter}");
+                ClientResponse::success(cid, CommandResult::from_log_level_result(LogLevelResult { filter }))
+            },
+            Err(e) => invalid(e),
+        }
+    }
+
+    fn _status(&self, cid: u16) -> ClientResponse {
+        // Command::Status 请求响应, 汇总连接、会话 与 统计信息
+        let clients = self.server.lifecycle.clients();
+        let sessions = self.server.sessions.lock().unwrap().len();
+        let res = self.server.stats.lock().unwrap().report(clients, sessions);
+        ClientResponse::success(cid, CommandResult::from_status_result(res))
+    }
+
+    fn _grammar_analysis(&mut self, cid: u16, req: ClientRequest) -> ClientResponse {
+        // Command::Analyze 请求响应
+
+        let params = match req.params.to_analyze_params() {
+            Ok(p) => p,
+            Err(e) => return ClientResponse::failure(cid, ResponseError::new(ErrorCode::InvalidParams, e)),
+        };
+        let comments = match self._comments(cid, params.document, &params.language, &params.code) {
+            Ok(c) => c,
+            Err(e) => return ClientResponse::failure(cid, e),
+        };
+        let cursor = self.position.to_byte_cursor(&params.code, &params.cursor);
+        let grammar = grammar_mode(comments.kind_at(&cursor, &params.code));
+        let range = self._context_range(&comments, comments.enclosing(&cursor, &params.code), &params.code);
+        // 可见区域内的所有上下文区间, 供客户端在本地判断
+        let ranges = params.visible.map(|visible| {
+            comments.contexts(&params.code).into_iter()
+                .filter(|c| c.range.start_point.row <= visible.end_row && c.range.end_point.row >= visible.start_row)
+                .map(|c| self._context_range(&comments, c, &params.code))
+                .collect()
+        });
+
+        let res = AnalyzeResult { grammar, range, ranges };
+        ClientResponse::success(cid, CommandResult::from_analyze_result(res))
+    }
+
+    fn _batch_analysis(&mut self, cid: u16, req: ClientRequest) -> ClientResponse {
+        // Command::AnalyzeBatch 请求响应
+        let params = match req.params.to_analyze_batch_params() {
+            Ok(p) => p,
+            Err(e) => return ClientResponse::failure(cid, ResponseError::new(ErrorCode::InvalidParams, e)),
+        };
+        match self._grammars(cid, params.document, &params.language, &params.code, &params.cursors) {
+            Ok(grammars) => ClientResponse::success(cid, CommandResult::from_analyze_batch_result(AnalyzeBatchResult { grammars })),
+            Err(e) => ClientResponse::failure(cid, e),
+        }
+    }
+}
+
+fn grammar_mode(kind: ContextKind) -> GrammarMode {
+    match kind {
+        ContextKind::Code => GrammarMode::Code,
+        ContextKind::Comment => GrammarMode::Comment,
+        ContextKind::Prose => GrammarMode::Prose,
+    }
+}
@@ -0,0 +1,148 @@
+//! 守护进程发现文件
+//!
+//! 守护进程启动后将端点写入运行时目录下的发现文件:
+//! ```json
+//! { "port": 40123, "pid": 4242, "token": "..." }
+//! ```
+//! 之后以守护进程模式启动的实例先读取发现文件, 并以文件中的令牌发送 Status 请求确认守护进程存活,
+//! 存活时直接输出其端点并退出; 否则视为残留文件, 删除后自行启动。
+//! 探测 与 启动期间持有同目录下 `.lock` 文件的排他锁, 避免同时启动的实例各自成为守护进程。
+//!
+//! 发现文件包含认证令牌, 仅当前用户可读, 守护进程退出时删除。
+
+use crate::rpc::*;
+
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io;
+use std::net::{Ipv4Addr, SocketAddr, TcpStream};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// 探测守护进程时 连接与读取响应的超时时间（毫秒）
+const PROBE_TIMEOUT_MS: u64 = 1000;
+/// 探测响应的最大字节数
+const PROBE_MAX_FRAME_SIZE: usize = 64 * 1024;
+
+/// 守护进程端点
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+pub(crate) struct Endpoint {
+    pub(crate) port: u16,
+    pub(crate) pid: u32,
+    pub(crate) token: String,
+}
+
+pub(crate) struct DiscoveryFile {
+    path: PathBuf,
+}
+impl DiscoveryFile {
+    pub(crate) fn new(path: PathBuf) -> DiscoveryFile {
+        DiscoveryFile { path }
+    }
+
+    /// 默认位置: $XDG_RUNTIME_DIR/lazy-input-switcher.json,
+    /// 未设置时使用系统临时目录, 并以用户名区分
+    pub(crate) fn default_path() -> PathBuf {
+        if let Some(dir) = std::env::var_os("XDG_RUNTIME_DIR").filter(|d| !d.is_empty()) {
+            return PathBuf::from(dir).join("lazy-input-switcher.json");
+        };
+        let user = std::env::var("USER").or_else(|_| std::env::var("USERNAME")).unwrap_or_default();
+        std::env::temp_dir().join(format!("lazy-input-switcher-{user}.json"))
+    }
+
+    pub(crate) fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub(crate) fn read(&self) -> Option<Endpoint> {
+        let content = fs::read(&self.path).ok()?;
+        serde_json::from_slice(&content).ok()
+    }
+
+    /// 获取发现文件的排他锁, 阻塞直到其他实例释放, 返回的文件关闭时释放锁
+    pub(crate) fn lock(&self) -> io::Result<File> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        };
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create(true).truncate(false);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        let file = options.open(self.path.with_extension("lock"))?;
+        file.lock()?;
+        Ok(file)
+    }
+
+    /// 查找存活的守护进程, 发现文件残留时将其删除
+    pub(crate) fn find_live(&self) -> Option<Endpoint> {
+        let endpoint = self.read()?;
+        if probe(&endpoint) {
+            return Some(endpoint);
+        };
+        log::info!("Removing stale discovery file {} (pid {})", self.path.display(), endpoint.pid);
+        let _ = fs::remove_file(&self.path);
+        None
+    }
+
+    /// 写入端点, 先写临时文件再重命名, 避免其他实例读取到不完整的内容
+    pub(crate) fn write(&self, endpoint: &Endpoint) -> io::Result<()> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        };
+        let temp = self.path.with_extension(format!("{}.tmp", endpoint.pid));
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        let mut file = options.open(&temp)?;
+        io::Write::write_all(&mut file, &serde_json::to_vec(endpoint)?)?;
+        drop(file);
+        fs::rename(&temp, &self.path)
+    }
+
+    /// 删除发现文件, 文件已被其他守护进程覆盖时保留
+    pub(crate) fn remove(&self, pid: u32) {
+        if self.read().is_some_and(|endpoint| endpoint.pid == pid) {
+            let _ = fs::remove_file(&self.path);
+        };
+    }
+}
+
+/// 以发现文件中的令牌请求状态, 确认端口上是存活的守护进程而不是复用了端口的其他程序
+/// cid 为 0 的 Status 请求不会在守护进程中分配会话
+fn probe(endpoint: &Endpoint) -> bool {
+    let timeout = Duration::from_millis(PROBE_TIMEOUT_MS);
+    let addr = SocketAddr::from((Ipv4Addr::LOCALHOST, endpoint.port));
+    let Ok(mut stream) = TcpStream::connect_timeout(&addr, timeout) else {
+        return false;
+    };
+    let request = serde_json::json!({
+        "token": endpoint.token, "cid": 0, "command": "Status", "params": {},
+    });
+    if send_message(&mut stream, &Encoding::Json.encode(&request)).is_err() {
+        return false;
+    };
+    // 等待响应同样需要超时, 避免端口被无响应的程序占用时阻塞启动
+    if stream.set_read_timeout(Some(timeout)).is_err() {
+        return false;
+    };
+    let mut len_buf = [0u8; 8];
+    if io::Read::read_exact(&mut stream, &mut len_buf).is_err() {
+        return false;
+    };
+    let size = u64::from_be_bytes(len_buf);
+    if size > PROBE_MAX_FRAME_SIZE as u64 {
+        return false;
+    };
+    let mut message = vec![0u8; size as usize];
+    if io::Read::read_exact(&mut stream, &mut message).is_err() {
+        return false;
+    };
+    Encoding::Json.decode::<ClientResponse>(&message).is_ok_and(|response| response.is_success())
+}
@@ -0,0 +1,162 @@
+//! 服务端生命周期: 连接登记、空闲超时 与 退出信号
+//!
+//! 监听线程阻塞在 accept 上, 需要退出时（Exit 指令、空闲超时、退出信号）
+//! 记录退出原因, 再向监听地址发起一次连接将其唤醒。
+//!
+//! 退出信号仅在 unix 平台处理: SIGTERM / SIGINT / SIGHUP,
+//! Windows 下结束后台进程无法被捕获。
+
+use std::collections::HashMap;
+use std::net::{Shutdown, SocketAddr, TcpStream};
+use std::sync::{Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+/// 唤醒监听线程时 连接的超时时间（毫秒）
+const WAKE_TIMEOUT_MS: u64 = 1000;
+
+/// 服务端退出的原因
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub(crate) enum StopReason {
+    /// 客户端发送 Exit 指令
+    Exit,
+    /// 所有客户端断开后 空闲超时
+    Idle,
+    /// 收到退出信号
+    Signal(i32),
+}
+
+pub(super) struct Lifecycle {
+    state: Mutex<State>,
+    /// 连接数变化 或 退出时通知
+    changed: Condvar,
+}
+struct State {
+    /// 活动连接, 退出时用于关闭连接
+    clients: HashMap<u64, TcpStream>,
+    next_id: u64,
+    /// 最后一个连接断开的时间
+    idle_since: Instant,
+    stop: Option<StopReason>,
+    /// 监听地址, 用于唤醒阻塞的 accept
+    addr: Option<SocketAddr>,
+}
+impl Lifecycle {
+    pub(super) fn new() -> Lifecycle {
+        let state = State {
+            clients: HashMap::new(), next_id: 0, idle_since: Instant::now(), stop: None, addr: None,
+        };
+        Lifecycle { state: Mutex::new(state), changed: Condvar::new() }
+    }
+
+    pub(super) fn bind(&self, addr: SocketAddr) {
+        self.state.lock().unwrap().addr = Some(addr);
+    }
+
+    /// 登记新连接, 服务端正在退出时返回 None
+    pub(super) fn register(&self, client: &TcpStream) -> Option<u64> {
+        let mut state = self.state.lock().unwrap();
+        if state.stop.is_some() {
+            return None;
+        };
+        let stream = client.try_clone().ok()?;
+        let id = state.next_id;
+        state.next_id += 1;
+        state.clients.insert(id, stream);
+        self.changed.notify_all();
+        Some(id)
+    }
+
+    /// 注销连接, 返回是否为最后一个连接
+    pub(super) fn unregister(&self, id: u64) -> bool {
+        let mut state = self.state.lock().unwrap();
+        state.clients.remove(&id);
+        let last = state.clients.is_empty();
+        if last {
+            state.idle_since = Instant::now();
+        };
+        self.changed.notify_all();
+        last
+    }
+
+    /// 当前活动的连接数
+    pub(super) fn clients(&self) -> usize {
+        self.state.lock().unwrap().clients.len()
+    }
+
+    pub(super) fn stop_reason(&self) -> Option<StopReason> {
+        self.state.lock().unwrap().stop
+    }
+
+    /// 记录退出原因并唤醒监听线程, 仅第一次调用生效
+    pub(super) fn stop(&self, reason: StopReason) {
+        let addr = {
+            let mut state = self.state.lock().unwrap();
+            if state.stop.is_some() {
+                return;
+            };
+            state.stop = Some(reason);
+            log::info!("Stopping server: {reason:?}");
+            self.changed.notify_all();
+            state.addr
+        };
+        if let Some(addr) = addr {
+            let _ = TcpStream::connect_timeout(&addr, Duration::from_millis(WAKE_TIMEOUT_MS));
+        };
+    }
+
+    /// 阻塞直至所有连接断开后空闲超时 或 服务端退出
+    pub(super) fn watch_idle(&self, timeout: Duration) {
+        let mut state = self.state.lock().unwrap();
+        while state.stop.is_none() {
+            if !state.clients.is_empty() {
+                state = self.changed.wait(state).unwrap();
+                continue;
+            };
+            let elapsed = state.idle_since.elapsed();
+            if elapsed >= timeout {
+                drop(state);
+                self.stop(StopReason::Idle);
+                return;
+            };
+            state = self.changed.wait_timeout(state, timeout - elapsed).unwrap().0;
+        }
+    }
+
+    /// 关闭所有连接, 并等待连接线程结束
+    pub(super) fn close_clients(&self, wait: Duration) {
+        let mut state = self.state.lock().unwrap();
+        for client in state.clients.values() {
+            let _ = client.shutdown(Shutdown::Both);
+        }
+        let deadline = Instant::now() + wait;
+        while !state.clients.is_empty() {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return;
+            };
+            state = self.changed.wait_timeout(state, remaining).unwrap().0;
+        }
+    }
+}
+
+/// 收到退出信号时停止服务端, 返回的句柄用于服务端退出后注销信号处理
+#[cfg(unix)]
+pub(super) fn watch_signals(server: std::sync::Arc<super::Sever>) -> Option<signal_hook::iterator::Handle> {
+    use signal_hook::consts::{SIGHUP, SIGINT, SIGTERM};
+    use signal_hook::iterator::Signals;
+
+    let mut signals = match Signals::new([SIGTERM, SIGINT, SIGHUP]) {
+        Ok(signals) => signals,
+        Err(e) => {
+            log::warn!("Failed to register signal handler: {e}");
+            return None;
+        }
+    };
+    let handle = signals.handle();
+    std::thread::spawn(move || {
+        if let Some(signal) = signals.forever().next() {
+            server.lifecycle.stop(StopReason::Signal(signal));
+        };
+    });
+    Some(handle)
+}
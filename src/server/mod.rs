@@ -0,0 +1,135 @@
+//! 服务端主体: 连接处理 与 命令分发
+//!
+//! 每个客户端连接由独立的连接线程处理, 连接线程负责读取请求与语法分析,
+//! 输入法操作交由共享的工作线程执行, 客户端可以流水线方式连续发送请求, 响应按请求 id 匹配。
+//!
+//! 守护进程模式下多个编辑器共享同一服务端, 端点信息写入发现文件。
+
+mod connection;
+mod discovery;
+mod lifecycle;
+mod session;
+mod stats;
+mod worker;
+
+use crate::config::Config;
+use crate::parser::GrammarRegistry;
+use crate::rpc::*;
+use connection::Connection;
+pub(super) use discovery::*;
+use lifecycle::*;
+pub(super) use lifecycle::StopReason;
+pub(super) use session::*;
+pub(super) use stats::*;
+use worker::*;
+
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+/// 退出时等待连接线程结束的时间（毫秒）
+const CLOSE_CLIENTS_WAIT_MS: u64 = 1000;
+/// 退出时等待工作线程还原输入法的时间（毫秒）
+const RESTORE_WAIT_MS: u64 = 3000;
+
+pub(crate) struct Sever {
+    config: Config,
+    token: AuthToken,
+    sessions: Arc<Mutex<SessionManager>>,
+    worker: SwitchWorker,
+    stats: Arc<Mutex<Stats>>,
+    lifecycle: Lifecycle,
+    tracer: Option<Tracer>,
+    grammars: Arc<GrammarRegistry>,
+}
+impl Sever {
+    pub(crate) fn new(config: Config, token: AuthToken) -> Sever {
+        let sessions = Arc::new(Mutex::new(SessionManager::new()));
+        let stats = Arc::new(Mutex::new(Stats::new()));
+        let worker = SwitchWorker::spawn(
+            sessions.clone(), stats.clone(), config.mock_switcher.then(|| (config.mock_switch_delay, config.mock_gate.clone())),
+        );
+        // trace 文件无法创建时仅记录错误, 不影响服务
+        let tracer = config.trace_file.as_ref().and_then(|path| match Tracer::create(path, config.trace_redact) {
+            Ok(tracer) => Some(tracer),
+            Err(e) => {
+                log::error!("Failed to create trace file {}: {e}", path.display());
+                None
+            },
+        });
+        let grammars = Arc::new(match &config.grammar_dir {
+            Some(dir) => GrammarRegistry::load(dir),
+            None => GrammarRegistry::default(),
+        });
+        Sever { config, token, sessions, worker, stats, lifecycle: Lifecycle::new(), tracer, grammars }
+    }
+
+    pub(crate) fn init_listener(&self) -> (u16, TcpListener) {
+        match init_socket() {
+            Ok((p, l)) => (p, l),
+            Err(e) => { panic!("Not found available port! {e}") }
+        }
+    }
+
+    /// 接受客户端连接, 每个连接在独立线程中处理, 直至收到退出指令、空闲超时 或 退出信号
+    pub(crate) fn serve(self: &Arc<Sever>, listener: &TcpListener) -> StopReason {
+        if let Ok(addr) = listener.local_addr() {
+            self.lifecycle.bind(addr);
+        };
+        if let Some(timeout) = self.config.idle_timeout {
+            let server = Arc::clone(self);
+            thread::spawn(move || server.lifecycle.watch_idle(timeout));
+        };
+        #[cfg(unix)]
+        let signals = watch_signals(Arc::clone(self));
+        // 阻塞等待连接, 退出时由 Lifecycle::stop 发起的连接唤醒
+        loop {
+            let client = accept_connect(listener);
+            if self.lifecycle.stop_reason().is_some() {
+                break;
+            };
+            if let Ok(client) = client {
+                self._spawn_connection(client);
+            };
+        }
+        #[cfg(unix)]
+        if let Some(signals) = signals {
+            signals.close();
+        };
+        let reason = self.lifecycle.stop_reason().unwrap_or(StopReason::Exit);
+        self._shutdown(reason);
+        reason
+    }
+
+    /// 在当前线程运行输入法工作循环, 直至 serve 结束, 需要由主线程调用;
+    /// 输入法后端不要求主线程时 工作循环已在独立线程中运行, 立即返回
+    pub(crate) fn run_worker(&self) {
+        self.worker.run_on_current_thread();
+    }
+
+    fn _spawn_connection(self: &Arc<Sever>, mut client: TcpStream) {
+        let Some(id) = self.lifecycle.register(&client) else {
+            return;
+        };
+        let server = Arc::clone(self);
+        thread::spawn(move || {
+            let result = Connection::new(server.clone(), id).handle_client(&mut client);
+            // 守护进程模式下仍有其他连接时, 退出指令只结束当前连接
+            let last = server.lifecycle.unregister(id);
+            if result.is_ok() && (last || !server.config.daemon) {
+                server.lifecycle.stop(StopReason::Exit);
+            };
+        });
+    }
+
+    fn _shutdown(&self, reason: StopReason) {
+        // 先关闭连接, 不再接收新的请求
+        self.lifecycle.close_clients(Duration::from_millis(CLOSE_CLIENTS_WAIT_MS));
+        // 被信号终止时还原服务端启动时的输入法, 排队中的请求被取消
+        if let StopReason::Signal(_) = reason {
+            self.worker.shutdown(Duration::from_millis(RESTORE_WAIT_MS));
+        };
+        self.worker.stop();
+    }
+}
@@ -0,0 +1,132 @@
+//! 客户端会话管理
+//!
+//! 会话与 TCP 连接解耦: 请求 cid 为 0 或未知时分配新会话,
+//! 客户端断线重连时携带旧 cid 即可取回文档缓存与输入法快照
+//!
+//! 文档缓存仅保存代码的哈希值与语法树，不保存源代码内容
+
+use crate::core::{InputMethodMode, SupportLanguage};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::time::Instant;
+use tree_sitter::Tree;
+
+/// 同时保留的最大会话数量, 超出时淘汰最久未活动 且没有连接绑定的会话
+const MAX_SESSIONS: usize = 32;
+
+/// 单个文档的语法树缓存
+struct Document {
+    language: SupportLanguage,
+    code_hash: u64,
+    tree: Tree,
+}
+
+pub(crate) struct Session {
+    pub(crate) cid: u16,
+    /// 以客户端提供的文档标识为键, 未提供时以语言名称为键
+    documents: HashMap<String, Document>,
+    /// 本会话最近一次确认的输入法状态, 会话恢复时据此还原
+    pub(crate) method: Option<InputMethodMode>,
+    last_active: Instant,
+    /// 绑定该会话的存活连接数量, 不为 0 时会话不会被淘汰
+    connections: usize,
+}
+impl Session {
+    fn new(cid: u16) -> Session {
+        Session { cid, documents: HashMap::new(), method: None, last_active: Instant::now(), connections: 0 }
+    }
+
+    /// 代码未发生变化时返回缓存的语法树
+    pub(crate) fn cached_tree(&self, document: &str, language: SupportLanguage, code: &str) -> Option<Tree> {
+        let doc = self.documents.get(document)?;
+        if doc.language == language && doc.code_hash == hash_code(code) {
+            Some(doc.tree.clone())
+        } else {
+            None
+        }
+    }
+
+    pub(crate) fn cache_tree(&mut self, document: String, language: SupportLanguage, code: &str, tree: Tree) {
+        let code_hash = hash_code(code);
+        self.documents.insert(document, Document { language, code_hash, tree });
+    }
+}
+
+pub(crate) struct SessionManager {
+    sessions: HashMap<u16, Session>,
+    current_cid: u16,
+}
+impl SessionManager {
+    pub(crate) fn new() -> SessionManager {
+        SessionManager { sessions: HashMap::new(), current_cid: 0 }
+    }
+
+    /// 根据请求中的 cid 查找会话 并绑定连接, 连接断开时需调用 release
+    ///
+    /// return: (会话 cid, 是否为恢复的已有会话)
+    pub(crate) fn resolve(&mut self, cid: u16) -> (u16, bool) {
+        if cid != 0 && let Some(session) = self.sessions.get_mut(&cid) {
+            session.connections += 1;
+            session.last_active = Instant::now();
+            return (cid, true);
+        };
+        if self.sessions.len() >= MAX_SESSIONS {
+            self._evict();
+        };
+        let cid = self._next_cid();
+        let mut session = Session::new(cid);
+        session.connections = 1;
+        self.sessions.insert(cid, session);
+        (cid, false)
+    }
+
+    /// 连接断开, 解除与会话的绑定
+    pub(crate) fn release(&mut self, cid: u16) {
+        if let Some(session) = self.sessions.get_mut(&cid) {
+            session.connections = session.connections.saturating_sub(1);
+            session.last_active = Instant::now();
+        }
+    }
+
+    pub(crate) fn get_mut(&mut self, cid: u16) -> Option<&mut Session> {
+        self.sessions.get_mut(&cid)
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.sessions.len()
+    }
+
+    pub(crate) fn touch(&mut self, cid: u16) {
+        if let Some(session) = self.sessions.get_mut(&cid) {
+            session.last_active = Instant::now();
+        }
+    }
+
+    fn _next_cid(&mut self) -> u16 {
+        // 跳过 0 与仍在使用中的 cid, 绑定了连接的会话不会被淘汰, 因此不会复用连接持有的 cid
+        loop {
+            self.current_cid = self.current_cid.wrapping_add(1);
+            if self.current_cid != 0 && !self.sessions.contains_key(&self.current_cid) {
+                return self.current_cid;
+            }
+        }
+    }
+
+    fn _evict(&mut self) {
+        // 所有会话均绑定了连接时不淘汰, 会话数量暂时超出上限
+        let oldest = self.sessions.values()
+            .filter(|s| s.connections == 0)
+            .min_by_key(|s| s.last_active)
+            .map(|s| s.cid);
+        if let Some(cid) = oldest {
+            self.sessions.remove(&cid);
+        }
+    }
+}
+
+fn hash_code(code: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    code.hash(&mut hasher);
+    hasher.finish()
+}
@@ -0,0 +1,153 @@
+//! 运行统计: 请求计数、耗时 与 输入法后端状态
+//!
+//! 由连接线程 与 工作线程共同记录, Status 请求时汇总。
+//! 耗时的百分位数基于最近 LATENCY_WINDOW 个样本, 次数 与 平均值基于全部样本。
+
+use crate::core::SupportLanguage;
+use crate::rpc::*;
+
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::fmt::Display;
+use std::time::{Duration, Instant};
+
+/// 计算百分位数时保留的最近样本数
+const LATENCY_WINDOW: usize = 1024;
+
+/// 耗时统计项
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub(crate) enum Metric {
+    /// 语法树构建
+    Parse,
+    /// 注释节点查询
+    Query,
+    /// 输入法后端切换
+    Switch,
+}
+
+#[derive(Default)]
+struct Latency {
+    samples: VecDeque<Duration>,
+    count: u64,
+    total: Duration,
+}
+impl Latency {
+    fn record(&mut self, elapsed: Duration) {
+        if self.samples.len() == LATENCY_WINDOW {
+            self.samples.pop_front();
+        };
+        self.samples.push_back(elapsed);
+        self.count += 1;
+        self.total += elapsed;
+    }
+
+    fn summary(&self) -> LatencySummary {
+        if self.count == 0 {
+            return LatencySummary::default();
+        };
+        let mut sorted: Vec<Duration> = self.samples.iter().copied().collect();
+        sorted.sort();
+        let percentile = |p: usize| {
+            // 最近秩法: 第 ceil(p% * n) 个样本
+            let rank = (p * sorted.len()).div_ceil(100).max(1);
+            sorted[rank - 1].as_micros() as u64
+        };
+        LatencySummary {
+            count: self.count,
+            avg_us: (self.total.as_micros() / self.count as u128) as u64,
+            p50_us: percentile(50),
+            p95_us: percentile(95),
+            p99_us: percentile(99),
+            max_us: sorted.last().map_or(0, |d| d.as_micros() as u64),
+        }
+    }
+}
+
+pub(crate) struct Stats {
+    started: Instant,
+    languages: BTreeSet<String>,
+    requests: BTreeMap<CommandMode, u64>,
+    parse: Latency,
+    query: Latency,
+    switch: Latency,
+    backend: Option<&'static str>,
+    healthy: bool,
+    last_error: Option<(String, Duration)>,
+}
+impl Stats {
+    pub(crate) fn new() -> Stats {
+        Stats {
+            started: Instant::now(),
+            languages: BTreeSet::new(),
+            requests: BTreeMap::new(),
+            parse: Latency::default(),
+            query: Latency::default(),
+            switch: Latency::default(),
+            backend: None,
+            healthy: false,
+            last_error: None,
+        }
+    }
+
+    pub(crate) fn record_request(&mut self, command: CommandMode) {
+        *self.requests.entry(command).or_default() += 1;
+    }
+
+    pub(crate) fn record_language(&mut self, language: SupportLanguage) {
+        self.languages.insert(language.to_string());
+    }
+
+    pub(crate) fn record_latency(&mut self, metric: Metric, elapsed: Duration) {
+        match metric {
+            Metric::Parse => self.parse.record(elapsed),
+            Metric::Query => self.query.record(elapsed),
+            Metric::Switch => self.switch.record(elapsed),
+        }
+    }
+
+    /// 输入法后端初始化结果
+    pub(crate) fn init_backend<E: Display>(&mut self, backend: Result<&'static str, E>) {
+        match backend {
+            Ok(name) => {
+                self.backend = Some(name);
+                self.healthy = true;
+            },
+            Err(e) => self.record_backend_error(e),
+        }
+    }
+
+    /// 记录输入法后端调用结果
+    pub(crate) fn record_backend<T, E: Display>(&mut self, result: &Result<T, E>) {
+        match result {
+            Ok(_) => self.healthy = self.backend.is_some(),
+            Err(e) => self.record_backend_error(e),
+        }
+    }
+
+    fn record_backend_error(&mut self, error: impl Display) {
+        self.healthy = false;
+        self.last_error = Some((error.to_string(), self.started.elapsed()));
+    }
+
+    pub(crate) fn report(&self, clients: usize, sessions: usize) -> StatusResult {
+        StatusResult {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            uptime_secs: self.started.elapsed().as_secs(),
+            clients,
+            sessions,
+            languages: self.languages.iter().cloned().collect(),
+            backend: BackendStatus {
+                name: self.backend.map(String::from),
+                healthy: self.healthy,
+                last_error: self.last_error.as_ref().map(|(message, at)| BackendError {
+                    message: message.clone(), uptime_secs: at.as_secs(),
+                }),
+            },
+            requests: self.requests.clone(),
+            latency: LatencyReport {
+                parse: self.parse.summary(),
+                query: self.query.summary(),
+                switch: self.switch.summary(),
+            },
+        }
+    }
+}
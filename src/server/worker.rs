@@ -0,0 +1,409 @@
+//! 输入法切换工作线程
+//!
+//! 输入法后端调用可能很慢（如 Linux 下需要启动 bash 脚本）,
+//! 所有输入法操作在独立线程中串行执行, 连接线程可在切换进行中继续处理语法分析请求。
+//! 工作线程直接向客户端写回响应, 响应通过请求 id 与请求对应。
+//!
+//! 执行前先取出所有已到达的任务: 处理 Cancel 请求,
+//! 并在同一会话同一文档存在多个 Switch 请求时仅保留最新的一个（latest wins）,
+//! 避免编辑器快速移动光标时逐个执行过时的切换。
+//!
+//! 服务端被信号终止时, 工作线程取消排队中的请求, 并还原服务端启动时的输入法。
+//!
+//! macOS 的 TIS 接口只能在主线程调用, 使用系统输入法后端时工作循环不创建新线程,
+//! 由主线程通过 SwitchWorker::run_on_current_thread 运行, 服务端监听改在其他线程进行。
+
+use super::session::SessionManager;
+use super::stats::{Metric, Stats};
+use crate::core::InputMethodMode;
+use crate::rpc::*;
+use crate::switch::{MockGate, Switcher};
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::error::Error;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// 存在订阅者时 轮询输入法后端的间隔（毫秒）
+const METHOD_POLL_INTERVAL_MS: u64 = 500;
+
+/// 交由工作线程执行的输入法操作
+pub(crate) enum Job {
+    /// 切换至指定输入法
+    MethodOnly(InputMethodMode),
+    /// 根据语法分析结果切换输入法, document 为客户端提供的文档标识
+    Switch { document: Option<String>, grammar: GrammarMode },
+    Subscribe,
+    Unsubscribe,
+    /// 取消同一会话中指定 id 且尚在排队的请求
+    Cancel(u64),
+    /// 会话恢复时还原输入法快照, 无响应
+    Restore(InputMethodMode),
+    /// 连接断开, 清理订阅, 无响应
+    Disconnect,
+}
+
+/// 工作线程接收的消息
+enum Message {
+    Task(Task),
+    /// 取消排队中的请求, 还原启动时的输入法后 结束工作线程
+    Shutdown(Sender<()>),
+    /// 服务端已退出, 直接结束工作线程
+    Stop,
+}
+
+pub(crate) struct Task {
+    pub(crate) cid: u16,
+    pub(crate) id: Option<u64>,
+    pub(crate) job: Job,
+    pub(crate) writer: MessageWriter,
+}
+
+/// 工作线程句柄
+pub(crate) struct SwitchWorker {
+    sender: Sender<Message>,
+    /// 需要在主线程运行的工作循环, 由 run_on_current_thread 取出执行
+    deferred: Mutex<Option<Box<dyn FnOnce() + Send>>>,
+}
+impl SwitchWorker {
+    /// mock 为模拟输入法后端每次切换的耗时 与 测试闸门, None 时使用系统输入法
+    pub(crate) fn spawn(
+        sessions: Arc<Mutex<SessionManager>>, stats: Arc<Mutex<Stats>>, mock: Option<(Duration, Option<MockGate>)>,
+    ) -> SwitchWorker {
+        let (sender, receiver) = mpsc::channel();
+        let main_thread = cfg!(target_os = "macos") && mock.is_none();
+        let body = move || {
+            // 输入法后端在工作线程内初始化, 失败时保存原因, 此时仍可提供语法分析服务
+            let switcher = match mock {
+                Some((delay, gate)) => Ok(Switcher::mock(delay, gate)),
+                None => Switcher::new().map_err(|e| format!("Switcher init failed: {e}")),
+            };
+            match &switcher {
+                Ok(s) => log::info!(target: "switch", "Input method backend: {}", s.name()),
+                Err(e) => log::error!(target: "switch", "{e}"),
+            };
+            stats.lock().unwrap().init_backend(switcher.as_ref().map(|s| s.name()));
+            let initial = switcher.as_ref().ok().and_then(|s| s.query().ok());
+            Worker { switcher, initial, sessions, stats, subscribers: HashMap::new() }.run(receiver);
+        };
+        if main_thread {
+            return SwitchWorker { sender, deferred: Mutex::new(Some(Box::new(body))) };
+        };
+        thread::spawn(body);
+        SwitchWorker { sender, deferred: Mutex::new(None) }
+    }
+
+    /// 在当前线程运行需要主线程的工作循环, 直至服务端退出; 工作循环已在独立线程中运行时立即返回
+    pub(crate) fn run_on_current_thread(&self) {
+        let body = self.deferred.lock().unwrap().take();
+        if let Some(body) = body {
+            body();
+        };
+    }
+
+    pub(crate) fn submit(&self, task: Task) {
+        // 工作线程仅在服务端退出时结束, 发送失败可忽略
+        let _ = self.sender.send(Message::Task(task));
+    }
+
+    /// 通知工作线程退出, 并等待其还原输入法, 输入法后端无响应时最多等待 timeout
+    pub(crate) fn shutdown(&self, timeout: Duration) {
+        let (ack, done) = mpsc::channel();
+        if self.sender.send(Message::Shutdown(ack)).is_ok() {
+            let _ = done.recv_timeout(timeout);
+        };
+    }
+
+    /// 通知工作线程结束, 不等待排队中的请求
+    pub(crate) fn stop(&self) {
+        let _ = self.sender.send(Message::Stop);
+    }
+}
+
+struct Worker {
+    switcher: Result<Switcher, String>,
+    /// 服务端启动时的输入法, 退出时还原
+    initial: Option<InputMethodMode>,
+    sessions: Arc<Mutex<SessionManager>>,
+    stats: Arc<Mutex<Stats>>,
+    /// 订阅了输入法变化通知的会话
+    subscribers: HashMap<u16, MessageWriter>,
+}
+impl Worker {
+    fn run(mut self, receiver: Receiver<Message>) {
+        let interval = Duration::from_millis(METHOD_POLL_INTERVAL_MS);
+        let mut next_poll = Instant::now() + interval;
+        let mut pending: VecDeque<Task> = VecDeque::new();
+        loop {
+            if pending.is_empty() {
+                let message = if self.subscribers.is_empty() {
+                    match receiver.recv() {
+                        Ok(message) => Some(message),
+                        Err(_) => return,
+                    }
+                } else {
+                    match receiver.recv_timeout(next_poll.saturating_duration_since(Instant::now())) {
+                        Ok(message) => Some(message),
+                        Err(RecvTimeoutError::Timeout) => None,
+                        Err(RecvTimeoutError::Disconnected) => return,
+                    }
+                };
+                if let Some(message) = message && !self._receive(message, &mut pending) {
+                    return;
+                };
+            };
+            // 取出所有已到达的任务, 以便执行前进行取消与合并
+            for message in receiver.try_iter() {
+                if !self._receive(message, &mut pending) {
+                    return;
+                };
+            }
+            self._cancel(&mut pending);
+            self._supersede(&mut pending);
+            if let Some(task) = pending.pop_front() {
+                self._handle(task);
+            };
+            if !self.subscribers.is_empty() && Instant::now() >= next_poll {
+                self._poll_method();
+                next_poll = Instant::now() + interval;
+            };
+        }
+    }
+
+    /// 任务加入队列, 收到退出消息时返回 false
+    fn _receive(&mut self, message: Message, pending: &mut VecDeque<Task>) -> bool {
+        match message {
+            Message::Task(task) => {
+                pending.push_back(task);
+                true
+            },
+            Message::Shutdown(ack) => {
+                self._shutdown(pending);
+                let _ = ack.send(());
+                false
+            },
+            Message::Stop => false,
+        }
+    }
+
+    fn _shutdown(&mut self, pending: &mut VecDeque<Task>) {
+        for task in pending.drain(..) {
+            if matches!(task.job, Job::Restore(_) | Job::Disconnect) {
+                continue;
+            };
+            let error = ResponseError::new(ErrorCode::Cancelled, "Server shutting down");
+            Worker::_reply(&task, ClientResponse::failure(task.cid, error));
+        }
+        if let (Ok(switcher), Some(initial)) = (&self.switcher, self.initial) {
+            match switcher.switch(initial) {
+                Ok(_) => log::info!(target: "switch", "Restored input method {initial}"),
+                Err(e) => log::warn!(target: "switch", "Restore input method {initial} failed: {e}"),
+            };
+        };
+    }
+
+    fn _cancel(&self, pending: &mut VecDeque<Task>) {
+        let mut cancels = Vec::new();
+        pending.retain(|task| match task.job {
+            Job::Cancel(target) => {
+                cancels.push((task.cid, task.id, target, task.writer.clone()));
+                false
+            },
+            _ => true,
+        });
+        for (cid, id, target, writer) in cancels {
+            // 只允许取消输入法切换类请求
+            let position = pending.iter().position(|task| {
+                task.cid == cid && task.id == Some(target) && matches!(task.job, Job::MethodOnly(_) | Job::Switch { .. })
+            });
+            let cancelled = match position.and_then(|i| pending.remove(i)) {
+                Some(task) => {
+                    let error = ResponseError::new(ErrorCode::Cancelled, format!("Request {target} cancelled"));
+                    Worker::_reply(&task, ClientResponse::failure(task.cid, error));
+                    true
+                },
+                None => false,
+            };
+            let response = ClientResponse::success(cid, CommandResult::from_cancel_result(CancelResult { cancelled }));
+            let _ = writer.send(&response.with_id(id));
+        }
+    }
+
+    fn _supersede(&self, pending: &mut VecDeque<Task>) {
+        // 从后向前遍历, 同一会话同一文档仅保留最后一个 Switch 请求, 未提供文档标识的请求不参与合并
+        let mut latest = HashSet::new();
+        let mut superseded = Vec::new();
+        for (i, task) in pending.iter().enumerate().rev() {
+            if let Job::Switch { document: Some(document), .. } = &task.job && !latest.insert((task.cid, document.clone())) {
+                superseded.push(i);
+            }
+        }
+        // superseded 为降序下标, 依次移除不影响剩余下标
+        for i in superseded {
+            if let Some(task) = pending.remove(i) {
+                let error = ResponseError::new(ErrorCode::Cancelled, "Superseded by a newer Switch request");
+                Worker::_reply(&task, ClientResponse::failure(task.cid, error));
+            }
+        }
+    }
+
+    fn _reply(task: &Task, response: ClientResponse) {
+        // 客户端断开时写回失败, 由连接线程处理断开
+        let _ = task.writer.send(&response.with_id(task.id));
+    }
+
+    fn _handle(&mut self, task: Task) {
+        let cid = task.cid;
+        let response = match &task.job {
+            Job::MethodOnly(mode) => self._method_only(cid, *mode),
+            Job::Switch { grammar, .. } => self._switch(cid, *grammar),
+            Job::Subscribe => {
+                let response = self._subscribe(cid);
+                if response.is_success() {
+                    self.subscribers.insert(cid, task.writer.clone());
+                };
+                response
+            },
+            Job::Unsubscribe => {
+                self.subscribers.remove(&cid);
+                ClientResponse::new(cid, true, None, None)
+            },
+            Job::Restore(mode) => {
+                // 还原失败不影响后续请求
+                if let Ok(switcher) = &self.switcher {
+                    let _ = switcher.switch(*mode);
+                };
+                return;
+            },
+            Job::Disconnect => {
+                self.subscribers.remove(&cid);
+                return;
+            },
+            // 取消请求在执行前已处理
+            Job::Cancel(_) => return,
+        };
+        Worker::_reply(&task, response);
+    }
+
+    fn _switcher(&self) -> Result<&Switcher, ResponseError> {
+        self.switcher.as_ref().map_err(|e| ResponseError::new(ErrorCode::BackendUnavailable, e))
+    }
+
+    fn _backend_switch(&self, switcher: &Switcher, target_mode: InputMethodMode) -> Result<bool, Box<dyn Error>> {
+        // 记录切换耗时 与 后端状态
+        let start = Instant::now();
+        let result = switcher.switch(target_mode);
+        let elapsed = start.elapsed();
+        match &result {
+            Ok(switched) => log::debug!(target: "switch", "Switch to {target_mode} ({switched}) in {elapsed:?}"),
+            Err(e) => log::warn!(target: "switch", "Switch to {target_mode} failed: {e}"),
+        };
+        let mut stats = self.stats.lock().unwrap();
+        stats.record_latency(Metric::Switch, elapsed);
+        stats.record_backend(&result);
+        result
+    }
+
+    fn _backend_query(&self, switcher: &Switcher) -> Result<InputMethodMode, Box<dyn Error>> {
+        let result = switcher.query();
+        if let Err(e) = &result {
+            log::warn!(target: "switch", "Query input method failed: {e}");
+        };
+        self.stats.lock().unwrap().record_backend(&result);
+        result
+    }
+
+    fn _remember_method(&self, cid: u16, method: InputMethodMode) {
+        if let Some(session) = self.sessions.lock().unwrap().get_mut(cid) {
+            session.method = Some(method);
+        }
+    }
+
+    fn _poll_method(&mut self) {
+        // 与各会话最近确认的输入法状态比较, 服务端自身的切换已同步记录, 不会触发通知
+        let method = match self._switcher().map(|s| self._backend_query(s)) {
+            Ok(Ok(method)) => method,
+            _ => return,
+        };
+        let mut sessions = self.sessions.lock().unwrap();
+        self.subscribers.retain(|cid, writer| {
+            let session = match sessions.get_mut(*cid) {
+                Some(session) => session,
+                None => return false,
+            };
+            if session.method == Some(method) {
+                return true;
+            };
+            session.method = Some(method);
+            writer.send(&ServerNotification::method_changed(*cid, method)).is_ok()
+        });
+    }
+
+    fn _method_only(&self, cid: u16, target_mode: InputMethodMode) -> ClientResponse {
+        // 处理 Command::MethodOnly 请求响应
+        let switcher = match self._switcher() {
+            Ok(s) => s,
+            Err(e) => return ClientResponse::failure(cid, e),
+        };
+        match self._backend_switch(switcher, target_mode) {
+            Ok(true) => {},
+            Ok(false) => return ClientResponse::failure(
+                cid, ResponseError::new(ErrorCode::SwitchFailed, "Switch input method failed"),
+            ),
+            Err(e) => return ClientResponse::failure(cid, ResponseError::new(ErrorCode::SwitchFailed, e)),
+        };
+
+        let res = match self._backend_query(switcher) {
+            Ok(method) => MethodOnlyResult { method },
+            Err(e) => return ClientResponse::failure(cid, ResponseError::new(ErrorCode::QueryFailed, e)),
+        };
+        self._remember_method(cid, res.method);
+        ClientResponse::success(cid, CommandResult::from_method_only_result(res))
+    }
+
+    fn _subscribe(&self, cid: u16) -> ClientResponse {
+        // 处理 Command::Subscribe 请求, 返回当前输入法状态作为后续通知的基准
+        let method = match self._switcher() {
+            Ok(switcher) => self._backend_query(switcher),
+            Err(e) => return ClientResponse::failure(cid, e),
+        };
+        match method {
+            Ok(method) => {
+                self._remember_method(cid, method);
+                ClientResponse::success(cid, CommandResult::from_method_only_result(MethodOnlyResult { method }))
+            },
+            Err(e) => ClientResponse::failure(cid, ResponseError::new(ErrorCode::QueryFailed, e)),
+        }
+    }
+
+    fn _switch(&self, cid: u16, comment: GrammarMode) -> ClientResponse {
+        // 处理 Command::Switch 请求, 语法分析已由连接线程完成
+        let switcher = match self._switcher() {
+            Ok(s) => s,
+            Err(e) => return ClientResponse::failure(cid, e),
+        };
+        // 根据 comment 决定是否切换输入法
+        let switch = match comment {
+            GrammarMode::Comment | GrammarMode::Prose => { self._backend_switch(switcher, InputMethodMode::Native) },
+            GrammarMode::Code => { self._backend_switch(switcher, InputMethodMode::English) }
+        };
+        let error = match switch {
+            Ok(true) => None,
+            Ok(false) => Some(ResponseError::new(ErrorCode::SwitchFailed, "Switch input method failed")),
+            Err(e) => Some(ResponseError::new(ErrorCode::SwitchFailed, e)),
+        };
+        let input_method = match self._backend_query(switcher) {
+            Ok(m) => m,
+            Err(e) => return ClientResponse::failure(cid, ResponseError::new(ErrorCode::QueryFailed, e)),
+        };
+        self._remember_method(cid, input_method);
+        let res = SwitchResult { grammar: comment, method: input_method };
+        match error {
+            // 切换失败时 语法分析结果 与 当前输入法 通过 data 返回
+            Some(e) => ClientResponse::failure(cid, e.with_data(serde_json::to_value(&res).unwrap())),
+            None => ClientResponse::success(cid, CommandResult::from_switch_result(res)),
+        }
+    }
+}
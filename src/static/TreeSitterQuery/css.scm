@@ -0,0 +1,4 @@
+; CSS 注释节点匹配
+(comment) @comment
+; SCSS / Less 的 // 单行注释
+(js_comment) @comment
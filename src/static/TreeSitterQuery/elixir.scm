@@ -0,0 +1,10 @@
+; Elixir 注释匹配规则
+(comment) @comment
+; @moduledoc / @doc / @typedoc 的文档字符串
+(unary_operator
+  operator: "@"
+  operand: (call
+    target: (identifier) @_attribute
+    (arguments
+      [(string) (charlist) (sigil)] @comment))
+  (#any-of? @_attribute "moduledoc" "typedoc" "doc"))
@@ -0,0 +1,4 @@
+; HTML 注释节点匹配
+(comment) @comment
+; 元素内的文本为正文
+(text) @prose
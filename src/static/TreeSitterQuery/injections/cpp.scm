@@ -0,0 +1,5 @@
+; cpp 语言注入规则
+; 原始字符串按分隔符对应的语言解析, 如 R"sql(SELECT ...)sql"
+(raw_string_literal
+  delimiter: (raw_string_delimiter) @injection.language
+  (raw_string_content) @injection.content)
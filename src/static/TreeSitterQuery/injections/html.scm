@@ -0,0 +1,9 @@
+; html 语言注入规则
+; <script> 按 javascript 解析, <style> 按 css 解析
+((script_element
+  (raw_text) @injection.content)
+  (#set! injection.language "javascript"))
+
+((style_element
+  (raw_text) @injection.content)
+  (#set! injection.language "css"))
@@ -0,0 +1,5 @@
+; java 语言注入规则
+; 以 SQL 关键字开头的字符串 与 文本块按 SQL 解析
+([(string_fragment) (multiline_string_fragment)] @injection.content
+  (#match? @injection.content "^\\s*(?i:select|insert|update|delete|create|alter|drop|with)\\s")
+  (#set! injection.language "sql"))
@@ -0,0 +1,5 @@
+; kotlin 语言注入规则
+; 以 SQL 关键字开头的字符串按 SQL 解析
+((string_content) @injection.content
+  (#match? @injection.content "^\\s*(?i:select|insert|update|delete|create|alter|drop|with)\\s")
+  (#set! injection.language "sql"))
@@ -0,0 +1,14 @@
+; lua 语言注入规则
+; LuaJIT ffi.cdef 中的字符串按 C 解析
+((function_call
+  name: [
+    (identifier) @_cdef_identifier
+    (_
+      _
+      (identifier) @_cdef_identifier)
+  ]
+  arguments: (arguments
+    (string
+      content: _ @injection.content)))
+  (#eq? @_cdef_identifier "cdef")
+  (#set! injection.language "c"))
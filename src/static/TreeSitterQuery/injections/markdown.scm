@@ -0,0 +1,10 @@
+; markdown 语言注入规则
+; 代码块按信息字符串声明的语言解析
+(fenced_code_block
+  (info_string
+    (language) @injection.language)
+  (code_fence_content) @injection.content)
+
+; 段落 与 标题内的行内语法
+((inline) @injection.content
+  (#set! injection.language "markdown_inline"))
@@ -0,0 +1,14 @@
+; php 语言注入规则
+; heredoc / nowdoc 按结束标识对应的语言解析, 如 <<<SQL ... SQL
+(heredoc
+  (heredoc_body) @injection.content
+  (heredoc_end) @injection.language)
+
+(nowdoc
+  (nowdoc_body) @injection.content
+  (heredoc_end) @injection.language)
+
+; php 标签之外的文本按 html 解析
+((text) @injection.content
+  (#set! injection.language "html")
+  (#set! injection.combined))
@@ -0,0 +1,2 @@
+; inherits: vue
+; svelte 语言注入规则 与 vue 相同
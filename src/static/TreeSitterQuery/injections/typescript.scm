@@ -0,0 +1,10 @@
+; typescript 语言注入规则
+; 带标签的模板字符串按标签名对应的语言解析, 如 sql`SELECT ...`
+(call_expression
+  function: [
+    (identifier) @injection.language
+    (member_expression
+      property: (property_identifier) @injection.language)
+  ]
+  arguments: (template_string (string_fragment) @injection.content)
+  (#set! injection.combined))
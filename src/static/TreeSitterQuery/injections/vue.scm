@@ -0,0 +1,32 @@
+; vue 语言注入规则
+; <script> / <style> 按 lang 属性声明的语言解析, 如 <script lang="ts">
+((script_element
+  (start_tag
+    (attribute
+      (attribute_name) @_lang
+      (quoted_attribute_value
+        (attribute_value) @injection.language)))
+  (raw_text) @injection.content)
+  (#eq? @_lang "lang"))
+
+((style_element
+  (start_tag
+    (attribute
+      (attribute_name) @_lang
+      (quoted_attribute_value
+        (attribute_value) @injection.language)))
+  (raw_text) @injection.content)
+  (#eq? @_lang "lang"))
+
+; 未声明 lang 时 <script> 为 javascript, <style> 为 css
+((script_element
+  (start_tag) @_tag
+  (raw_text) @injection.content)
+  (#not-match? @_tag "\\slang\\s*=")
+  (#set! injection.language "javascript"))
+
+((style_element
+  (start_tag) @_tag
+  (raw_text) @injection.content)
+  (#not-match? @_tag "\\slang\\s*=")
+  (#set! injection.language "css"))
@@ -0,0 +1,2 @@
+; JSON / JSONC 注释节点匹配
+(comment) @comment
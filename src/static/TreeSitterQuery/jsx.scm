@@ -0,0 +1,6 @@
+; JSX 注释节点匹配
+; {/* */} 中的注释同为 comment 节点
+(comment) @comment
+(html_comment) @comment
+; 标签内的文本为正文
+(jsx_text) @prose
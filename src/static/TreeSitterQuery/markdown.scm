@@ -0,0 +1,15 @@
+; 正文匹配规则
+; 整个文档默认为正文, 代码块、HTML 块 与 Front Matter 为代码
+(document) @prose
+[
+  (fenced_code_block)
+  (indented_code_block)
+  (minus_metadata)
+  (plus_metadata)
+  (link_destination)
+] @code
+; 注释匹配规则
+((html_block) @comment
+  (#match? @comment "^<!--"))
+((html_block) @code
+  (#not-match? @code "^<!--"))
@@ -0,0 +1,15 @@
+; 正文匹配规则
+; 段落 与 标题内的文本为正文, 行内代码、链接地址 与 公式为代码
+(inline) @prose
+[
+  (code_span)
+  (link_destination)
+  (uri_autolink)
+  (email_autolink)
+  (latex_block)
+] @code
+; 注释匹配规则
+((html_tag) @comment
+  (#match? @comment "^<!--"))
+((html_tag) @code
+  (#not-match? @code "^<!--"))
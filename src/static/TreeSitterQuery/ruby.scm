@@ -0,0 +1,10 @@
+; Ruby 注释匹配规则
+; 包含 # 单行注释 与 =begin / =end 文档注释
+(comment) @comment
+; 结束标识为说明文字的 heredoc（如 desc <<~DESC）视为文档, 插值表达式 与 结束标识为代码
+; 其余 heredoc 多为 SQL、脚本 或 模板, 仍为代码
+((heredoc_body
+  (heredoc_end) @_end) @comment
+  (#match? @_end "^\\s*(DOC|DESC|DESCRIPTION|HELP|USAGE|BANNER|MESSAGE|MSG|TEXT)$"))
+(heredoc_body
+  [(interpolation) (heredoc_end)] @code)
@@ -0,0 +1,5 @@
+; inherits: html
+; 模板文本中的 { } 表达式 与 {#if} 等逻辑块为代码
+((text) @prose
+  (#set! interpolation.start "{")
+  (#set! interpolation.end "}"))
@@ -0,0 +1,16 @@
+; Swift 注释匹配规则
+; 包含 /// 与 /** */ 文档注释
+(comment) @comment
+(multiline_comment) @comment
+; 作为说明文字的多行字符串视为文档, 如 CommandConfiguration(abstract:discussion:) 与 @available(message:)
+; 其余多行字符串多为 SQL、JSON 或 模板, 仍为代码
+(value_argument
+  name: (value_argument_label) @_label
+  value: (multi_line_string_literal) @comment
+  (#any-of? @_label "abstract" "discussion" "help" "usage" "message"))
+(property_declaration
+  name: (pattern) @_name
+  value: (multi_line_string_literal) @comment
+  (#any-of? @_name "abstract" "discussion" "help" "usage" "description"))
+(multi_line_string_literal
+  interpolation: (interpolated_expression) @code)
@@ -0,0 +1,2 @@
+; TOML 注释节点匹配
+(comment) @comment
@@ -0,0 +1,5 @@
+; TSX 注释节点绑定
+; {/* */} 中的注释同为 comment 节点
+(comment) @comment
+; 标签内的文本为正文
+(jsx_text) @prose
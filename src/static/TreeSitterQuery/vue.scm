@@ -0,0 +1,5 @@
+; inherits: html
+; 模板文本中的 {{ }} 插值表达式为代码
+((text) @prose
+  (#set! interpolation.start "{{")
+  (#set! interpolation.end "}}"))
@@ -0,0 +1,2 @@
+; YAML 注释节点匹配
+(comment) @comment
@@ -51,6 +51,10 @@ impl Fcitx5Method {
     }
 }
 impl MethodController for Fcitx5Method {
+    fn name(&self) -> &'static str {
+        "fcitx5"
+    }
+
     fn switch_mode(&self, target_mode: InputMethodMode) -> Result<bool, Box<dyn Error>> {
         match target_mode {
             InputMethodMode::Native => {
@@ -31,7 +31,9 @@ impl StaticLinuxMethodShell {
         if output.status.success() {
             Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
         } else {
-            Err(format!("Script failed: {}", String::from_utf8_lossy(&output.stderr)).into())
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            log::warn!("Script {name} failed with {}: {}", output.status, stderr.trim());
+            Err(format!("Script failed: {stderr}").into())
         }
     }
 }
@@ -42,10 +44,8 @@ pub(super) enum SupportMethod {
 impl SupportMethod {
     pub(super) fn check_input_method() -> Option<SupportMethod> {
         let fcitx5 = StaticLinuxMethodShell::run_script("fcitx5/check", None);
-        if fcitx5.is_ok() {
-            if fcitx5.unwrap().to_lowercase() == "fcitx5" {
-                return Some(SupportMethod::Fcitx5)
-            }
+        if let Ok(name) = fcitx5 && name.to_lowercase() == "fcitx5" {
+            return Some(SupportMethod::Fcitx5)
         };
         None
     }
@@ -25,6 +25,10 @@ impl LinuxController {
         }
     }
 
+    pub(super) fn name(&self) -> &'static str {
+        self.method.name()
+    }
+
     pub(super) fn query(&self) -> Result<InputMethodMode, Box<dyn Error>> {
         self.method.get_mode()
     }
@@ -35,6 +39,9 @@ impl LinuxController {
 }
 
 trait MethodController {
+    /// 输入法框架名称
+    fn name(&self) -> &'static str;
+
     fn switch_mode(&self, target_mode: InputMethodMode) -> Result<bool, Box<dyn Error>>;
 
     fn get_mode(&self) -> Result<InputMethodMode, Box<dyn Error>>;
@@ -25,6 +25,10 @@ impl MacOSController {
         Ok(MacOSController { native, english })
     }
 
+    pub(super) fn name(&self) -> &'static str {
+        "macos-tis"
+    }
+
     pub(super) fn query(&self) -> Result<InputMethodMode, Box<dyn Error>> {
         let mode = get_mode()?;
         if mode == self.native {
@@ -0,0 +1,67 @@
+//! 模拟输入法后端
+//!
+//! 仅在内存中记录当前输入法, 切换总是成功, 用于请求回放 与 无输入法环境下的测试
+
+use crate::core::InputMethodMode;
+use std::cell::Cell;
+use std::error::Error;
+use std::fmt;
+use std::sync::mpsc::{Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+/// 测试用的切换闸门: 每次切换开始时通知测试端, 并等待测试端放行后才完成切换
+#[derive(Clone)]
+pub(crate) struct MockGate {
+    entered: Sender<InputMethodMode>,
+    release: Arc<Mutex<Receiver<()>>>,
+}
+impl MockGate {
+    /// 返回闸门 与 测试端持有的 切换开始通知、放行通道, 放行通道被丢弃后切换不再等待
+    #[cfg(test)]
+    pub(crate) fn new() -> (MockGate, Receiver<InputMethodMode>, Sender<()>) {
+        let (entered, entered_receiver) = std::sync::mpsc::channel();
+        let (release_sender, release) = std::sync::mpsc::channel();
+        (MockGate { entered, release: Arc::new(Mutex::new(release)) }, entered_receiver, release_sender)
+    }
+
+    fn _pass(&self, target_mode: InputMethodMode) {
+        let _ = self.entered.send(target_mode);
+        let _ = self.release.lock().unwrap().recv();
+    }
+}
+impl fmt::Debug for MockGate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("MockGate")
+    }
+}
+
+pub(super) struct MockController {
+    mode: Cell<InputMethodMode>,
+    /// 每次切换的耗时, 模拟启动外部程序等慢速后端
+    delay: Duration,
+    gate: Option<MockGate>,
+}
+impl MockController {
+    pub(super) fn new(delay: Duration, gate: Option<MockGate>) -> MockController {
+        MockController { mode: Cell::new(InputMethodMode::English), delay, gate }
+    }
+
+    pub(super) fn name(&self) -> &'static str {
+        "mock"
+    }
+
+    pub(super) fn query(&self) -> Result<InputMethodMode, Box<dyn Error>> {
+        Ok(self.mode.get())
+    }
+
+    pub(super) fn switch(&self, target_mode: InputMethodMode) -> Result<bool, Box<dyn Error>> {
+        thread::sleep(self.delay);
+        if let Some(gate) = &self.gate {
+            gate._pass(target_mode);
+        };
+        self.mode.set(target_mode);
+        Ok(true)
+    }
+}
@@ -12,62 +12,94 @@ mod macos;
 #[cfg(target_os = "linux")]
 mod linux;
 
+mod mock;
+pub(crate) use mock::MockGate;
+
 use crate::core::InputMethodMode;
 use std::error::Error;
+use std::time::Duration;
 
 pub(super) struct Switcher {
+    controller: Controller,
+}
+enum Controller {
     #[cfg(target_os = "windows")]
-    windows_controller: windows::WinInputMethodController,
+    Windows(windows::WinInputMethodController),
 
     #[cfg(target_os = "linux")]
-    linux_controller: linux::LinuxController,
+    Linux(linux::LinuxController),
 
     #[cfg(target_os = "macos")]
-    macos_controller: macos::MacOSController,
+    MacOS(macos::MacOSController),
+
+    /// 不操作系统输入法, 用于回放与测试
+    Mock(mock::MockController),
 }
 impl Switcher {
     pub(super) fn new() -> Result<Switcher, Box<dyn Error>> {
         #[cfg(target_os = "windows")]
-        match windows::WinInputMethodController::new() {
-            Ok(windows_controller) => Ok(Switcher { windows_controller }),
-            Err(err) => Err(err),
-        }
+        let controller = Controller::Windows(windows::WinInputMethodController::new()?);
 
         #[cfg(target_os = "linux")]
-        match linux::LinuxController::new() {
-            Ok(linux_controller) => Ok(Switcher { linux_controller }),
-            Err(err) => Err(err),
-        }
+        let controller = Controller::Linux(linux::LinuxController::new()?);
 
         #[cfg(target_os = "macos")]
-        match macos::MacOSController::new() {
-            Ok(macos_controller) => Ok(Switcher { macos_controller }),
-            Err(err) => Err(err),
+        let controller = Controller::MacOS(macos::MacOSController::new()?);
+
+        Ok(Switcher { controller })
+    }
+
+    /// 模拟输入法后端, 初始为 English, 每次切换耗时 delay, 并经过测试闸门 gate
+    pub(super) fn mock(delay: Duration, gate: Option<MockGate>) -> Switcher {
+        Switcher { controller: Controller::Mock(mock::MockController::new(delay, gate)) }
+    }
+
+    /// 输入法后端名称
+    pub(super) fn name(&self) -> &'static str {
+        match &self.controller {
+            #[cfg(target_os = "windows")]
+            Controller::Windows(controller) => controller.name(),
+
+            #[cfg(target_os = "linux")]
+            Controller::Linux(controller) => controller.name(),
+
+            #[cfg(target_os = "macos")]
+            Controller::MacOS(controller) => controller.name(),
+
+            Controller::Mock(controller) => controller.name(),
         }
     }
 
     pub(super) fn query(&self) -> Result<InputMethodMode, Box<dyn Error>> {
-        #[cfg(target_os = "windows")]
-        return self.windows_controller.get_mode();
+        match &self.controller {
+            #[cfg(target_os = "windows")]
+            Controller::Windows(controller) => controller.get_mode(),
 
-        #[cfg(target_os = "linux")]
-        return self.linux_controller.query();
+            #[cfg(target_os = "linux")]
+            Controller::Linux(controller) => controller.query(),
 
-        #[cfg(target_os = "macos")]
-        return self.macos_controller.query();
+            #[cfg(target_os = "macos")]
+            Controller::MacOS(controller) => controller.query(),
+
+            Controller::Mock(controller) => controller.query(),
+        }
     }
 
     pub(super) fn switch(&self, target_mode: InputMethodMode) -> Result<bool, Box<dyn Error>> {
         let mode = self.query()?;
         if target_mode != mode {
-            #[cfg(target_os = "windows")]
-            return self.windows_controller.switch_mode(target_mode);
+            return match &self.controller {
+                #[cfg(target_os = "windows")]
+                Controller::Windows(controller) => controller.switch_mode(target_mode),
 
-            #[cfg(target_os = "linux")]
-            return self.linux_controller.switch(target_mode);
+                #[cfg(target_os = "linux")]
+                Controller::Linux(controller) => controller.switch(target_mode),
 
-            #[cfg(target_os = "macos")]
-            return self.macos_controller.switch(target_mode);
+                #[cfg(target_os = "macos")]
+                Controller::MacOS(controller) => controller.switch(target_mode),
+
+                Controller::Mock(controller) => controller.switch(target_mode),
+            };
         };
         Ok(true)
     }
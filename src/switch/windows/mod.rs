@@ -43,6 +43,10 @@ impl WinInputMethodController {
         Ok(Self { native, english })
     }
 
+    pub(super) fn name(&self) -> &'static str {
+        "win32-keyboard-layout"
+    }
+
     pub(super) fn get_mode(&self) -> Result<InputMethodMode, Box<dyn Error>> {
         let handle = get_ground_handle()?;
         let active = get_active_language(&handle);
@@ -0,0 +1,122 @@
+use super::*;
+use crate::core::InputMethodMode;
+use crate::switch::MockGate;
+use serde_json::{json, Value};
+use std::time::Duration;
+
+#[test]
+fn analyze_batch_cursors() {
+    let (port, token, _) = spawn_server(&["--mock-switcher"]);
+    let mut client = connect(port);
+    let code = "// head\nfn main() {\n    let s = 1; /* tail */\n}";
+    let response = request(&mut client, json!({
+        "token": token.as_str(), "cid": 0, "command": "AnalyzeBatch",
+        "params": {
+            "code": code, "language": "Rust",
+            "cursors": [
+                { "row": 0, "column": 4 },
+                { "row": 1, "column": 3 },
+                { "row": 2, "column": 16 },
+                { "row": 2, "column": 4 },
+            ],
+        },
+    }));
+    assert_eq!(response["result"]["grammars"], json!(["Comment", "Code", "Comment", "Code"]));
+
+    // 与逐个 Analyze 的结果一致
+    let response = request(&mut client, json!({
+        "cid": 1, "command": "Analyze",
+        "params": { "code": code, "language": "Rust", "cursor": { "row": 2, "column": 16 } },
+    }));
+    assert_eq!(response["result"]["grammar"], "Comment");
+
+    let response = request(&mut client, json!({
+        "cid": 1, "command": "AnalyzeBatch",
+        "params": { "code": code, "language": "Nope", "cursors": [] },
+    }));
+    assert_eq!(response["error"]["code"], "UnsupportedLanguage");
+}
+
+#[test]
+fn analyze_context_ranges() {
+    let (port, token, _) = spawn_server(&["--mock-switcher"]);
+    let mut client = connect(port);
+    let code = "// head\nfn main() {\n    let s = 1; /* tail */\n}";
+    let response = request(&mut client, json!({
+        "token": token.as_str(), "cid": 0, "command": "Analyze",
+        "params": { "code": code, "language": "Rust", "cursor": { "row": 1, "column": 3 } },
+    }));
+    // 光标所在代码区间为两段注释之间
+    assert_eq!(response["result"]["range"], json!({
+        "grammar": "Code", "start": { "row": 0, "column": 7 }, "end": { "row": 2, "column": 15 },
+        "start_byte": 7, "end_byte": 35, "start_inclusive": false, "end_inclusive": true,
+    }));
+    assert_eq!(response["result"]["ranges"], Value::Null);
+
+    let response = request(&mut client, json!({
+        "cid": 1, "command": "Analyze",
+        "params": {
+            "code": code, "language": "Rust", "cursor": { "row": 2, "column": 20 },
+            "visible": { "start_row": 2, "end_row": 3 },
+        },
+    }));
+    assert_eq!(response["result"]["range"]["grammar"], "Comment");
+    assert_eq!((response["result"]["range"]["start_byte"].as_u64(), response["result"]["range"]["end_byte"].as_u64()), (Some(35), Some(45)));
+    let ranges: Vec<_> = response["result"]["ranges"].as_array().unwrap().iter()
+        .map(|r| (r["grammar"].as_str().unwrap(), r["start_byte"].as_u64().unwrap(), r["end_byte"].as_u64().unwrap()))
+        .collect();
+    assert_eq!(ranges, [("Code", 7, 35), ("Comment", 35, 45), ("Code", 45, 47)]);
+}
+
+#[test]
+fn analyze_utf16_position() {
+    let (port, token, _) = spawn_server(&["--mock-switcher"]);
+    let mut client = connect(port);
+    let response = request(&mut client, json!({
+        "token": token.as_str(), "cid": 0, "command": "Handshake",
+        "params": { "encoding": "Json", "position_encoding": "Utf16" },
+    }));
+    assert_eq!(response["result"]["position_encoding"], "Utf16");
+
+    // utf-16 第 15 列位于 // 之后, 按字节偏移则位于字符串内
+    let code = "let s = \"中文\"; // 😀 注释\nlet t = 1;";
+    let cursor = |column| json!({ "row": 0, "column": column });
+    let response = request(&mut client, json!({
+        "cid": 1, "command": "AnalyzeBatch",
+        "params": { "code": code, "language": "Rust", "cursors": [cursor(13), cursor(15), cursor(18), cursor(22)] },
+    }));
+    assert_eq!(response["result"]["grammars"], json!(["Code", "Comment", "Comment", "Comment"]));
+
+    let response = request(&mut client, json!({
+        "cid": 1, "command": "Analyze",
+        "params": { "code": code, "language": "Rust", "cursor": cursor(20) },
+    }));
+    assert_eq!(response["result"]["range"], json!({
+        "grammar": "Comment", "start": { "row": 0, "column": 14 }, "end": { "row": 0, "column": 22 },
+        "start_byte": 18, "end_byte": 32, "start_inclusive": false, "end_inclusive": true,
+    }));
+}
+
+#[test]
+fn analyze_answered_during_switch() {
+    let (gate, entered, release) = MockGate::new();
+    let (port, token, _) = spawn_server_with(Config { mock_switcher: true, mock_gate: Some(gate), ..Config::default() });
+    let mut client = connect(port);
+    let code = "fn main() { // hi\n}";
+    let params = json!({ "code": code, "language": "Rust", "cursor": { "row": 0, "column": 15 } });
+    let response = request(&mut client, json!({ "token": token.as_str(), "cid": 0, "command": "Analyze", "params": params }));
+    let cid = response["cid"].clone();
+
+    // 流水线发送: 切换阻塞在闸门中时, 连接线程直接回答 Analyze
+    send(&mut client, json!({ "cid": cid, "id": 1, "command": "Switch", "params": params }));
+    send(&mut client, json!({ "cid": cid, "id": 2, "command": "Analyze", "params": params }));
+    assert_eq!(entered.recv_timeout(Duration::from_secs(3)), Ok(InputMethodMode::Native));
+    let response = recv(&mut client);
+    assert_eq!(response["id"], 2);
+    assert_eq!(response["result"]["grammar"], "Comment");
+
+    drop(release);
+    let response = recv(&mut client);
+    assert_eq!(response["id"], 1);
+    assert_eq!(response["result"]["method"], "Native");
+}
@@ -0,0 +1,70 @@
+use super::*;
+use crate::config::Config;
+use crate::server::*;
+use serde_json::json;
+use std::path::PathBuf;
+use std::time::Duration;
+
+fn discovery_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("lazy-input-switcher-test-{}-{name}.json", std::process::id()))
+}
+
+#[test]
+fn stale_discovery_file_removed() {
+    let discovery = DiscoveryFile::new(discovery_path("stale"));
+    // 端口上没有守护进程, 发现文件视为残留
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    drop(listener);
+    let endpoint = Endpoint { port, pid: 1, token: "stale".to_string() };
+    discovery.write(&endpoint).unwrap();
+    assert_eq!(discovery.read(), Some(endpoint));
+
+    assert!(discovery.find_live().is_none());
+    assert!(!discovery.path().exists());
+}
+
+#[test]
+fn live_daemon_detected() {
+    let discovery = DiscoveryFile::new(discovery_path("live"));
+    assert!(Config::from_args(["--daemon"].map(String::from)).unwrap().daemon);
+    let (port, token, _) = spawn_server(&["--daemon"]);
+
+    let endpoint = Endpoint { port, pid: std::process::id(), token: token.as_str().to_string() };
+    discovery.write(&endpoint).unwrap();
+    assert_eq!(discovery.find_live(), Some(endpoint.clone()));
+    // 探测请求不分配会话
+    let mut client = connect(port);
+    let response = request(&mut client, json!({ "token": token.as_str(), "cid": 0, "command": "Status", "params": {} }));
+    assert_eq!((response["cid"].clone(), response["result"]["sessions"].clone()), (json!(0), json!(0)));
+
+    // 令牌不匹配时不视为存活的守护进程
+    let wrong = Endpoint { token: "wrong".to_string(), ..endpoint };
+    discovery.write(&wrong).unwrap();
+    assert!(discovery.find_live().is_none());
+
+    // 文件被其他守护进程覆盖时不删除
+    discovery.write(&Endpoint { pid: 1, ..wrong }).unwrap();
+    discovery.remove(std::process::id());
+    assert!(discovery.path().exists());
+    discovery.remove(1);
+    assert!(!discovery.path().exists());
+}
+
+#[test]
+fn discovery_lock_is_exclusive() {
+    let path = discovery_path("lock");
+    let lock = DiscoveryFile::new(path.clone()).lock().unwrap();
+    // 其他实例等待锁释放后才能探测 与 启动
+    let (sender, receiver) = std::sync::mpsc::channel();
+    let waiting = std::thread::spawn(move || {
+        let lock = DiscoveryFile::new(path).lock().unwrap();
+        sender.send(()).unwrap();
+        lock
+    });
+    assert!(receiver.recv_timeout(Duration::from_millis(200)).is_err());
+    drop(lock);
+    receiver.recv_timeout(Duration::from_secs(1)).unwrap();
+    drop(waiting.join().unwrap());
+    std::fs::remove_file(discovery_path("lock").with_extension("lock")).unwrap();
+}
@@ -0,0 +1,110 @@
+use crate::config::Config;
+use crate::parser::{GrammarRegistry, Parser};
+use std::env::consts::DLL_EXTENSION;
+use std::fs;
+use std::sync::Arc;
+
+#[test]
+fn grammar_dir_from_args() {
+    let config = Config::from_args(["--grammar-dir", "/opt/grammars"].map(String::from)).unwrap();
+    assert_eq!(config.grammar_dir.unwrap().to_str(), Some("/opt/grammars"));
+    assert!(Config::from_args(["--grammar-dir"].map(String::from)).is_err());
+}
+
+#[test]
+fn invalid_grammars_are_skipped() {
+    let dir = std::env::temp_dir().join(format!("lazy-input-switcher-grammars-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    // 无效的动态库、缺少 query 文件、与内置语言同名
+    fs::write(dir.join(format!("broken.{DLL_EXTENSION}")), b"not a library").unwrap();
+    fs::write(dir.join("broken.scm"), "(comment) @comment").unwrap();
+    fs::write(dir.join(format!("noquery.{DLL_EXTENSION}")), b"not a library").unwrap();
+    fs::write(dir.join(format!("rust.{DLL_EXTENSION}")), b"not a library").unwrap();
+
+    let parser = Parser::new(Arc::new(GrammarRegistry::load(&dir)));
+    fs::remove_dir_all(&dir).unwrap();
+    assert!(parser.language("broken").is_none());
+    assert!(parser.language("noquery").is_none());
+    assert_eq!(parser.language("Rust").map(|l| l.to_string()).as_deref(), Some("rust"));
+}
+
+/// 从 cargo 缓存中的 tree-sitter-json 源码编译语法动态库, 导出符号重命名为 tree_sitter_{name}
+#[cfg(unix)]
+fn build_json_grammar(dir: &std::path::Path, name: &str) -> Result<(), String> {
+    let home = std::env::var_os("CARGO_HOME").map(std::path::PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| std::path::Path::new(&home).join(".cargo")));
+    let source = home.and_then(|home| fs::read_dir(home.join("registry").join("src")).ok())
+        .into_iter().flatten().filter_map(|entry| fs::read_dir(entry.ok()?.path()).ok())
+        .flatten().filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .find(|path| path.file_name().and_then(|n| n.to_str()).is_some_and(|n| n.starts_with("tree-sitter-json-")))
+        .map(|path| path.join("src"));
+    let source = source.ok_or("tree-sitter-json sources not found in the cargo registry")?;
+    let status = std::process::Command::new("cc")
+        .args(["-shared", "-fPIC", "-O0"])
+        .arg(format!("-Dtree_sitter_json=tree_sitter_{name}"))
+        .arg("-I").arg(&source)
+        .arg(source.join("parser.c"))
+        .arg("-o").arg(dir.join(format!("{name}.{DLL_EXTENSION}")))
+        .status().map_err(|e| format!("failed to run cc: {e}"))?;
+    status.success().then_some(()).ok_or(format!("cc exited with {status}"))
+}
+
+/// 编译真实的语法动态库并加载, 依赖 C 编译器 与 cargo 缓存中的语法源码,
+/// 以 cargo test -- --ignored analyze_with_loaded_grammar 运行
+#[cfg(unix)]
+#[test]
+#[ignore = "compiles tree-sitter-json from the cargo registry with cc"]
+fn analyze_with_loaded_grammar() {
+    use super::{connect, request, spawn_server};
+    use serde_json::{json, Value};
+
+    let dir = std::env::temp_dir().join(format!("lazy-input-switcher-dynjson-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    build_json_grammar(&dir, "dynjson").unwrap();
+    // 注释以正文捕获, 区别于内置 JSON 语法的规则
+    fs::write(dir.join("dynjson.scm"), "(comment) @prose").unwrap();
+
+    let (port, token, _) = spawn_server(&["--mock-switcher", "--grammar-dir", dir.to_str().unwrap()]);
+    fs::remove_dir_all(&dir).unwrap();
+    let mut client = connect(port);
+
+    let code = "{\n  // 注释\n  \"a\": 1\n}";
+    let mut analyze = |row: usize, column: usize| -> Value {
+        let params = json!({ "code": code, "language": "DynJson", "cursor": { "row": row, "column": column } });
+        request(&mut client, json!({ "token": token.as_str(), "cid": 0, "command": "Analyze", "params": params }))
+    };
+    assert_eq!(analyze(1, 6)["result"]["grammar"], "Prose");
+    assert_eq!(analyze(2, 4)["result"]["grammar"], "Code");
+}
+
+/// grammars/ 目录中的查询规则需能在对应语法上编译, 否则加载时整个语法被跳过
+/// 先执行 grammars/build.sh <dir> 编译语法, 再以
+/// LAZY_INPUT_SWITCHER_GRAMMAR_DIR=<dir> cargo test -- --ignored shipped_queries_compile 运行
+#[test]
+#[ignore = "requires grammars built by grammars/build.sh in $LAZY_INPUT_SWITCHER_GRAMMAR_DIR"]
+fn shipped_queries_compile() {
+    let built = std::env::var_os("LAZY_INPUT_SWITCHER_GRAMMAR_DIR")
+        .map(std::path::PathBuf::from)
+        .expect("LAZY_INPUT_SWITCHER_GRAMMAR_DIR is not set");
+    let queries = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("grammars");
+    let mut names: Vec<String> = fs::read_dir(&queries).unwrap()
+        .filter_map(|entry| {
+            let path = entry.ok()?.path();
+            (path.extension()? == "scm").then(|| path.file_stem()?.to_str().map(str::to_string))?
+        })
+        .collect();
+    names.sort();
+
+    // 语法动态库 与 仓库中的查询规则放入同一目录后加载
+    let dir = std::env::temp_dir().join(format!("lazy-input-switcher-shipped-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    for name in &names {
+        let library = format!("{name}.{DLL_EXTENSION}");
+        let _ = fs::copy(built.join(&library), dir.join(&library));
+        fs::copy(queries.join(format!("{name}.scm")), dir.join(format!("{name}.scm"))).unwrap();
+    }
+    let parser = Parser::new(Arc::new(GrammarRegistry::load(&dir)));
+    fs::remove_dir_all(&dir).unwrap();
+    let failed: Vec<_> = names.iter().filter(|name| parser.language(name).is_none()).collect();
+    assert!(failed.is_empty(), "grammars not built or queries rejected: {failed:?}");
+}
@@ -0,0 +1,54 @@
+use super::*;
+use crate::config::Config;
+use crate::server::*;
+use std::io::Read;
+use std::time::Duration;
+
+#[test]
+fn idle_timeout_from_args() {
+    let config = Config::from_args(["--idle-timeout", "never"].map(String::from)).unwrap();
+    assert_eq!(config.idle_timeout, None);
+    let config = Config::from_args(["--idle-timeout", "30"].map(String::from)).unwrap();
+    assert_eq!(config.idle_timeout, Some(Duration::from_secs(30)));
+    assert_eq!(Config::default().idle_timeout, Some(Duration::from_secs(300)));
+
+    assert!(Config::from_args(["--idle-timeout", "0"].map(String::from)).is_err());
+    assert!(Config::from_args(["--idle-timeout", "soon"].map(String::from)).is_err());
+}
+
+#[test]
+fn idle_timeout_after_last_client() {
+    let (port, _, stopped) = spawn_server(&["--idle-timeout", "1"]);
+    // 连接存在期间不计入空闲时间
+    let client = connect(port);
+    assert!(stopped.recv_timeout(Duration::from_millis(1500)).is_err());
+    drop(client);
+    assert_eq!(stopped.recv_timeout(Duration::from_secs(3)), Ok(StopReason::Idle));
+}
+
+#[test]
+fn exit_closes_other_clients() {
+    let (port, token, stopped) = spawn_server(&["--idle-timeout", "never"]);
+    let mut other = connect(port);
+    let mut client = connect(port);
+    send(&mut client, serde_json::json!({
+        "token": token.as_str(), "cid": 0, "command": "Exit", "params": null,
+    }));
+    assert_eq!(stopped.recv_timeout(Duration::from_secs(3)), Ok(StopReason::Exit));
+
+    // 退出时其他连接被关闭
+    other.set_read_timeout(Some(Duration::from_secs(1))).unwrap();
+    let mut buffer = [0u8; 8];
+    assert_eq!(other.read(&mut buffer).unwrap(), 0);
+}
+
+#[test]
+fn unauthenticated_client_dropped() {
+    let (port, _, stopped) = spawn_server(&["--idle-timeout", "1", "--auth-timeout", "1"]);
+    // 不发送任何消息的连接在认证超时后被断开, 不阻止空闲退出
+    let mut client = connect(port);
+    client.set_read_timeout(Some(Duration::from_secs(3))).unwrap();
+    let mut buffer = [0u8; 8];
+    assert_eq!(client.read(&mut buffer).unwrap(), 0);
+    assert_eq!(stopped.recv_timeout(Duration::from_secs(3)), Ok(StopReason::Idle));
+}
@@ -0,0 +1,51 @@
+use crate::config::Config;
+use crate::logger::*;
+use log::{Level, LevelFilter};
+use std::fs;
+
+#[test]
+fn log_filter_per_module() {
+    let mut filter: LogFilter = "warn,rpc=debug".parse().unwrap();
+    assert!(filter.enabled("rpc", Level::Debug));
+    assert!(!filter.enabled("rpc", Level::Trace));
+    assert!(filter.enabled("parser", Level::Warn));
+    assert!(!filter.enabled("parser", Level::Info));
+    assert_eq!(filter.max_level(), LevelFilter::Debug);
+
+    // 运行时修改在原有规则上覆盖
+    filter.apply("switch=trace").unwrap();
+    assert_eq!(filter.to_string(), "warn,rpc=debug,switch=trace");
+    assert_eq!(filter.max_level(), LevelFilter::Trace);
+
+    // 规则错误时保持原有规则不变
+    assert!(filter.apply("info,rpc=loud").is_err());
+    assert_eq!(filter.to_string(), "warn,rpc=debug,switch=trace");
+
+    let config = Config::from_args(["--log-level", "debug", "--log-stderr"].map(String::from)).unwrap();
+    assert_eq!(config.log_filter.to_string(), "debug");
+    assert!(config.log_stderr);
+}
+
+#[test]
+fn log_module_from_target() {
+    assert_eq!(module_of("LazyInputSwitcher::rpc::socket"), "rpc");
+    assert_eq!(module_of("LazyInputSwitcher::switch::linux::lib"), "switch");
+    assert_eq!(module_of("LazyInputSwitcher"), "main");
+    assert_eq!(module_of("switch"), "switch");
+}
+
+#[test]
+fn rotating_log_file() {
+    let dir = std::env::temp_dir().join(format!("lazy-input-switcher-log-test-{}", std::process::id()));
+    let path = dir.join("server.log");
+    let mut file = RotatingFile::open(&path, 32, 2).unwrap();
+    for i in 0..4 {
+        file.write_line(&format!("line {i} 0123456789")).unwrap();
+    }
+    // 每个文件仅容纳一行, 最旧的一行被丢弃
+    assert_eq!(fs::read_to_string(&path).unwrap(), "line 3 0123456789\n");
+    assert_eq!(fs::read_to_string(dir.join("server.log.1")).unwrap(), "line 2 0123456789\n");
+    assert_eq!(fs::read_to_string(dir.join("server.log.2")).unwrap(), "line 1 0123456789\n");
+    assert!(!dir.join("server.log.3").exists());
+    fs::remove_dir_all(dir).unwrap();
+}
@@ -1,3 +1,56 @@
 mod parser_tests;
 mod rpc_tests;
 mod parse_load_tests;
+mod session_tests;
+mod discovery_tests;
+mod lifecycle_tests;
+mod stats_tests;
+mod logger_tests;
+mod socket_tests;
+mod trace_tests;
+mod analyze_tests;
+mod position_tests;
+mod grammar_tests;
+mod worker_tests;
+
+use crate::config::Config;
+use crate::rpc::*;
+use crate::server::*;
+use serde_json::Value;
+use std::net::TcpStream;
+use std::sync::Arc;
+use std::sync::mpsc;
+use std::time::Duration;
+
+/// 在后台线程运行服务端, 返回端口、令牌 与 服务端退出原因的接收端
+fn spawn_server(args: &[&str]) -> (u16, AuthToken, mpsc::Receiver<StopReason>) {
+    spawn_server_with(Config::from_args(args.iter().map(|a| a.to_string())).unwrap())
+}
+
+fn spawn_server_with(config: Config) -> (u16, AuthToken, mpsc::Receiver<StopReason>) {
+    let token = AuthToken::generate().unwrap();
+    let server = Arc::new(Sever::new(config, token.clone()));
+    let (port, listener) = server.init_listener();
+    let (sender, receiver) = mpsc::channel();
+    std::thread::spawn(move || sender.send(server.serve(&listener)));
+    (port, token, receiver)
+}
+
+fn connect(port: u16) -> TcpStream {
+    TcpStream::connect(("127.0.0.1", port)).unwrap()
+}
+
+fn send(client: &mut TcpStream, request: Value) {
+    send_message(client, &Encoding::Json.encode(&request)).unwrap();
+}
+
+/// 接收一条响应, 超时视为测试失败
+fn recv(client: &mut TcpStream) -> Value {
+    let message = recv_message(client, 1024 * 1024, Some(Duration::from_secs(3)), Duration::from_secs(3)).unwrap();
+    Encoding::Json.decode(&message).unwrap()
+}
+
+fn request(client: &mut TcpStream, request: Value) -> Value {
+    send(client, request);
+    recv(client)
+}
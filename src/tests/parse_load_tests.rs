@@ -1,12 +1,13 @@
 use crate::core::{Cursor, SupportLanguage};
 use crate::parser::Parser;
+use std::sync::Arc;
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
     fn run_test(lang: SupportLanguage, code: &str, row: usize, col: usize) {
-        let mut parser = Parser::new();
+        let mut parser = Parser::new(Arc::default());
         let code_str = code.to_string();
         parser.add_language(lang);
         parser.build_tree(lang, &code_str);
@@ -15,7 +16,7 @@ mod tests {
         let cursor = Cursor { row, column: col };
 
         // 只要运行不崩溃即代表通过
-        let _result = comments.in_range(&cursor, &code_str);
+        let _result = comments.kind_at(&cursor, &code_str);
     }
 
     #[test]
@@ -127,4 +128,108 @@ mod tests {
             5,
         );
     }
+
+    #[test]
+    fn test_ruby() {
+        run_test(SupportLanguage::Ruby, "# Ruby comment\nputs 'hello'", 0, 5);
+    }
+
+    #[test]
+    fn test_swift() {
+        run_test(SupportLanguage::Swift, "// Swift comment\nlet a = 1", 0, 5);
+    }
+
+    #[test]
+    fn test_elixir() {
+        run_test(SupportLanguage::Elixir, "# Elixir comment\nIO.puts(\"hello\")", 0, 5);
+    }
+
+    #[test]
+    fn test_tsx() {
+        run_test(SupportLanguage::Tsx, "// TSX comment\nconst a = <div>text</div>;", 1, 16);
+    }
+
+    #[test]
+    fn test_jsx() {
+        run_test(SupportLanguage::Jsx, "// JSX comment\nconst a = <div>text</div>;", 1, 16);
+    }
+
+    #[test]
+    fn test_html() {
+        run_test(SupportLanguage::Html, "<!-- HTML comment -->\n<p>text</p>", 1, 4);
+    }
+
+    #[test]
+    fn test_css() {
+        run_test(SupportLanguage::Css, "/* CSS comment */\na { color: red; }", 0, 5);
+    }
+
+    #[test]
+    fn test_vue() {
+        run_test(SupportLanguage::Vue, "<template><p>text</p></template>\n<script>// comment</script>", 1, 12);
+    }
+
+    #[test]
+    fn test_svelte() {
+        run_test(SupportLanguage::Svelte, "<script>// comment</script>\n<p>text</p>", 0, 12);
+    }
+
+    #[test]
+    fn test_yaml() {
+        run_test(SupportLanguage::Yaml, "# YAML comment\nkey: value", 0, 5);
+    }
+
+    #[test]
+    fn test_toml() {
+        run_test(SupportLanguage::Toml, "# TOML comment\nkey = 1", 0, 5);
+    }
+
+    #[test]
+    fn test_json() {
+        run_test(SupportLanguage::Json, "// JSONC comment\n{\"key\": 1}", 0, 5);
+    }
+
+    #[test]
+    fn test_markdown() {
+        run_test(SupportLanguage::Markdown, "# Title\n\n```rust\n// comment\n```\n", 3, 5);
+    }
+
+    #[test]
+    fn language_names_round_trip() {
+        // 输出的语言名称可再次解析为同一语言
+        let languages = [
+            SupportLanguage::Rust,
+            SupportLanguage::Python,
+            SupportLanguage::Lua,
+            SupportLanguage::C,
+            SupportLanguage::Java,
+            SupportLanguage::JavaScript,
+            SupportLanguage::Kotlin,
+            SupportLanguage::TypeScript,
+            SupportLanguage::Cpp,
+            SupportLanguage::Go,
+            SupportLanguage::Bash,
+            SupportLanguage::Sql,
+            SupportLanguage::Php,
+            SupportLanguage::CSharp,
+            SupportLanguage::Ruby,
+            SupportLanguage::Swift,
+            SupportLanguage::Elixir,
+            SupportLanguage::Tsx,
+            SupportLanguage::Jsx,
+            SupportLanguage::Html,
+            SupportLanguage::Css,
+            SupportLanguage::Vue,
+            SupportLanguage::Svelte,
+            SupportLanguage::Yaml,
+            SupportLanguage::Toml,
+            SupportLanguage::Json,
+            SupportLanguage::Markdown,
+            SupportLanguage::MarkdownInline,
+        ];
+        for lang in languages {
+            assert_eq!(SupportLanguage::from_string(&lang.to_string()), Some(lang), "{lang}");
+        }
+        assert_eq!(SupportLanguage::from_string("markdown_inline"), Some(SupportLanguage::MarkdownInline));
+    }
 }
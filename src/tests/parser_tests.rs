@@ -1,5 +1,6 @@
 use crate::core::*;
 use crate::parser::*;
+use std::sync::Arc;
 
 impl Cursor {
     pub fn new(row: usize, column: usize) -> Cursor {
@@ -20,7 +21,7 @@ impl CommentCheck {
 }
 
 fn run_comment_test(lang: SupportLanguage, code: String, checks: &[CommentCheck]) {
-    let mut parser = Parser::new();
+    let mut parser = Parser::new(Arc::default());
     parser.add_language(lang);
     parser.build_tree(lang, &code);
 
@@ -29,7 +30,7 @@ fn run_comment_test(lang: SupportLanguage, code: String, checks: &[CommentCheck]
     for check in checks {
         let except = if check.in_comment { "comment" } else { "code" };
         assert_eq!(
-            comments.in_range(&Cursor::new(check.row, check.col), &code),
+            comments.kind_at(&Cursor::new(check.row, check.col), &code) != ContextKind::Code,
             check.in_comment,
             "{:?}: Test Failed at position ({}, {}) Except {}"
             , lang, check.row, check.col, except
@@ -89,3 +90,199 @@ print("lua test")
     ];
     run_comment_test(lang, code, &checks);
 }
+
+#[test]
+fn python_sql_injection() {
+    let code = r#"
+query = """
+SELECT id -- 主键
+FROM users
+"""
+def f():
+    """Select the rows."""
+    return query  # note
+"#.to_string();
+    let checks = [
+        // 注入的 SQL
+        CommentCheck::new(2, 3, false),
+        CommentCheck::new(2, 12, true),
+        CommentCheck::new(3, 2, false),
+        // 文档字符串不作为 SQL 解析
+        CommentCheck::new(6, 9, true),
+        // 宿主语言的注释
+        CommentCheck::new(7, 4, false),
+        CommentCheck::new(7, 20, true),
+    ];
+    run_comment_test(SupportLanguage::Python, code, &checks);
+}
+
+#[test]
+fn javascript_tagged_template_injection() {
+    let code = "const q = sql`SELECT 1 -- one\n FROM t`; // js\n".to_string();
+    let checks = [
+        CommentCheck::new(0, 16, false),
+        CommentCheck::new(0, 26, true),
+        CommentCheck::new(1, 2, false),
+        CommentCheck::new(1, 13, true),
+    ];
+    run_comment_test(SupportLanguage::JavaScript, code, &checks);
+}
+
+#[test]
+fn injection_contexts() {
+    let code = "x = 1\nq = \"SELECT 1 -- one\"\n";
+    let lang = SupportLanguage::Python;
+    let mut parser = Parser::new(Arc::default());
+    parser.add_language(lang);
+    parser.build_tree(lang, code);
+    let comments = parser.get_comments(lang, code);
+    // 注入区间内的 SQL 注释, 其余为 Python 代码
+    let contexts: Vec<_> = comments.contexts(code).iter()
+        .map(|c| (c.kind == ContextKind::Comment, c.range.start_byte, c.range.end_byte))
+        .collect();
+    assert_eq!(contexts, [(false, 0, 20), (true, 20, 26), (false, 26, 28)]);
+    let enclosing = comments.enclosing(&Cursor::new(1, 16), code);
+    assert_eq!(enclosing.kind, ContextKind::Comment);
+    assert_eq!((enclosing.range.start_byte, enclosing.range.end_byte), (20, 26));
+}
+
+#[test]
+fn comment_end_column() {
+    let code = "let a = 1; /* c */ x;\n/* d */\n";
+    let lang = SupportLanguage::Rust;
+    let mut parser = Parser::new(Arc::default());
+    parser.add_language(lang);
+    parser.build_tree(lang, code);
+    let comments = parser.get_comments(lang, code);
+
+    // 注释之后同一行仍有代码: 结束位置属于其后的代码区间
+    let cursor = Cursor::new(0, 18);
+    assert_eq!(comments.kind_at(&cursor, code), ContextKind::Code);
+    let enclosing = comments.enclosing(&cursor, code);
+    assert_eq!((enclosing.kind, enclosing.range.start_byte), (ContextKind::Code, 18));
+    assert_eq!(comments.includes(&enclosing, code), (true, true));
+    let comment = comments.contexts(code).into_iter().find(|c| c.kind == ContextKind::Comment).unwrap();
+    assert_eq!((comment.range.start_byte, comment.range.end_byte), (11, 18));
+    assert_eq!(comments.includes(&comment, code), (false, false));
+
+    // 注释位于行尾: 结束位置仍属于注释
+    let cursor = Cursor::new(1, 7);
+    assert_eq!(comments.kind_at(&cursor, code), ContextKind::Comment);
+    let enclosing = comments.enclosing(&cursor, code);
+    assert_eq!((enclosing.kind, enclosing.range.start_byte, enclosing.range.end_byte), (ContextKind::Comment, 22, 29));
+    assert_eq!(comments.includes(&enclosing, code), (false, true));
+}
+
+fn kinds_at(lang: SupportLanguage, code: &str, cursors: &[(usize, usize)]) -> Vec<ContextKind> {
+    let mut parser = Parser::new(Arc::default());
+    parser.add_language(lang);
+    parser.build_tree(lang, code);
+    let comments = parser.get_comments(lang, code);
+    cursors.iter().map(|(row, column)| comments.kind_at(&Cursor::new(*row, *column), code)).collect()
+}
+
+#[test]
+fn markdown_prose_contexts() {
+    let code = "---\ntitle: x\n---\n# 标题 `code`\n\nHello [link](http://a.b) world.\n\n```rust\n// 注释\nlet x = 1;\n```\n<!-- note -->\n";
+    use ContextKind::*;
+    // Front Matter、标题、行内代码、段落、链接地址、代码块内的注释与代码、HTML 注释
+    let cursors = [(1, 3), (3, 4), (3, 11), (5, 3), (5, 15), (5, 27), (8, 5), (9, 5), (11, 6)];
+    assert_eq!(
+        kinds_at(SupportLanguage::Markdown, code, &cursors),
+        [Code, Prose, Code, Prose, Code, Prose, Comment, Code, Comment],
+    );
+}
+
+#[test]
+fn tsx_prose_and_comments() {
+    let code = "const A = () => (\n  <div title=\"t\">\n    你好 world\n    {/* 注释 */}\n    {count}\n  </div>\n);\n";
+    use ContextKind::*;
+    // 属性值、标签文本、{/* */} 注释、表达式
+    let cursors = [(1, 15), (2, 4), (2, 14), (3, 8), (4, 6)];
+    for lang in [SupportLanguage::Tsx, SupportLanguage::Jsx] {
+        assert_eq!(kinds_at(lang, code, &cursors), [Code, Prose, Prose, Comment, Code], "{lang:?}");
+    }
+}
+
+#[test]
+fn html_and_sfc_injections() {
+    use ContextKind::*;
+    let code = "<!-- 注释 -->\n<p class=\"a\">你好</p>\n<script>let a = 1; // js</script>\n<style>a { color: red; } /* css */</style>\n";
+    // HTML 注释、属性、文本、<script> 与 <style> 内的代码和注释
+    let cursors = [(0, 6), (1, 10), (1, 15), (2, 12), (2, 23), (3, 12), (3, 30)];
+    assert_eq!(
+        kinds_at(SupportLanguage::Html, code, &cursors),
+        [Comment, Code, Prose, Code, Comment, Code, Comment],
+    );
+
+    let script = "<script lang=\"ts\">\nconst a: number = 1; // ts\n</script>\n<style lang=\"scss\">\n// scss\n</style>\n";
+    // 模板文本为正文, 插值表达式为代码
+    let cases = [
+        (SupportLanguage::Vue, "<template>\n  <p>你好 {{ msg }} 世界</p>\n</template>\n"),
+        (SupportLanguage::Svelte, "<p>你好 {msg} 世界</p>\n{#if ok}<b>好</b>{/if}\n"),
+    ];
+    for (lang, template) in cases {
+        let code = format!("{template}{script}");
+        let rows = template.lines().count();
+        let (line, _) = template.lines().enumerate().find(|(_, l)| l.contains("msg")).unwrap();
+        let text = template.lines().nth(line).unwrap();
+        let msg = text.find("msg").unwrap();
+        let hello = text.find("你好").unwrap() + 3;
+        let world = text.find("世界").unwrap() + 3;
+        let cursors = [(line, hello), (line, msg + 1), (line, world), (rows + 1, 8), (rows + 1, 25), (rows + 4, 4)];
+        assert_eq!(
+            kinds_at(lang, &code, &cursors),
+            [Prose, Code, Prose, Code, Comment, Comment],
+            "{lang:?}",
+        );
+    }
+    let code = "{#if ok}<b>好</b>{/if}\n";
+    assert_eq!(kinds_at(SupportLanguage::Svelte, code, &[(0, 3), (0, 11)]), [Code, Prose]);
+}
+
+#[test]
+fn config_language_comments() {
+    let cases = [
+        (SupportLanguage::Yaml, "# 注释\nkey: value # 行尾\n"),
+        (SupportLanguage::Toml, "# 注释\nkey = \"value\" # 行尾\n"),
+        (SupportLanguage::Json, "// 注释\n{\"key\": \"value\"} // 行尾\n"),
+    ];
+    for (lang, code) in cases {
+        let tail = code.lines().nth(1).unwrap().len();
+        let checks = [
+            CommentCheck::new(0, 4, true),
+            CommentCheck::new(1, 2, false),
+            CommentCheck::new(1, tail, true),
+        ];
+        run_comment_test(lang, code.to_string(), &checks);
+    }
+}
+
+#[test]
+fn ruby_swift_elixir_comments() {
+    let code = "# 注释\ndef a\n  \"# str\"\nend\n=begin\n文档\n=end\n".to_string();
+    let checks = [CommentCheck::new(0, 4, true), CommentCheck::new(2, 4, false), CommentCheck::new(5, 3, true)];
+    run_comment_test(SupportLanguage::Ruby, code, &checks);
+
+    let code = "/// 文档\nfunc a() {\n  let s = \"// str\"\n  /* 块 */\n}\n".to_string();
+    let checks = [CommentCheck::new(0, 5, true), CommentCheck::new(2, 13, false), CommentCheck::new(3, 6, true)];
+    run_comment_test(SupportLanguage::Swift, code, &checks);
+
+    let code = "defmodule A do\n  @moduledoc \"\"\"\n  模块文档\n  \"\"\"\n  # 注释\n  def a, do: \"# str\"\nend\n".to_string();
+    let checks = [CommentCheck::new(2, 4, true), CommentCheck::new(4, 5, true), CommentCheck::new(5, 16, false)];
+    run_comment_test(SupportLanguage::Elixir, code, &checks);
+}
+
+#[test]
+fn ruby_swift_doc_strings() {
+    use ContextKind::*;
+    // 说明文字 heredoc 的文本、插值、结束标识, 以及 SQL heredoc
+    let code = "desc <<~DESC\n  你好 #{name}\nDESC\nsql = <<~SQL\n  SELECT 1\nSQL\n";
+    let cursors = [(0, 2), (1, 3), (1, 12), (2, 1), (4, 3)];
+    assert_eq!(kinds_at(SupportLanguage::Ruby, code, &cursors), [Code, Comment, Code, Code, Code]);
+
+    // abstract: 参数 与 help 属性的多行字符串、插值, 以及其他多行字符串
+    let code = "let c = CommandConfiguration(abstract: \"\"\"\n  你好 \\(name)\n  \"\"\")\nlet sql = \"\"\"\n  SELECT 1\n  \"\"\"\nlet help = \"\"\"\n  说明\n  \"\"\"\n";
+    let cursors = [(0, 3), (1, 3), (1, 12), (4, 3), (7, 3)];
+    assert_eq!(kinds_at(SupportLanguage::Swift, code, &cursors), [Code, Comment, Code, Code, Comment]);
+}
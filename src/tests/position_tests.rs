@@ -0,0 +1,39 @@
+use crate::core::*;
+
+// 字节偏移: 中 9, 文 12, // 18, 😀 21, 注 26
+// utf-16:  中 9, 文 10, // 14, 😀 17, 注 20
+// 码点:    中 9, 文 10, // 14, 😀 17, 注 19
+const LINE: &str = "let s = \"中文\"; // 😀 注释";
+
+#[test]
+fn utf16_to_byte_cursor() {
+    let code = format!("fn main() {{\n{LINE}\n}}");
+    let to_byte = |column| PositionEncoding::Utf16.to_byte_cursor(&code, &Cursor::new(1, column)).column;
+    assert_eq!(to_byte(10), 12);
+    assert_eq!(to_byte(14), 18);
+    // 落在代理对中间时取字符起始位置
+    assert_eq!(to_byte(17), 21);
+    assert_eq!(to_byte(18), 21);
+    assert_eq!(to_byte(20), 26);
+    // 超出行尾时取行尾
+    assert_eq!(to_byte(100), LINE.len());
+}
+
+#[test]
+fn codepoint_to_byte_cursor() {
+    let to_byte = |column| PositionEncoding::Utf32.to_byte_cursor(LINE, &Cursor::new(0, column)).column;
+    assert_eq!(to_byte(10), 12);
+    assert_eq!(to_byte(17), 21);
+    assert_eq!(to_byte(18), 25);
+    assert_eq!(to_byte(19), 26);
+}
+
+#[test]
+fn byte_cursor_to_client() {
+    for (byte, utf16, utf32) in [(0, 0, 0), (12, 10, 10), (18, 14, 14), (25, 19, 18), (26, 20, 19), (LINE.len(), 22, 21)] {
+        let cursor = Cursor::new(0, byte);
+        assert_eq!(PositionEncoding::Utf16.to_client_cursor(LINE, &cursor).column, utf16);
+        assert_eq!(PositionEncoding::Utf32.to_client_cursor(LINE, &cursor).column, utf32);
+        assert_eq!(PositionEncoding::Utf8.to_client_cursor(LINE, &cursor).column, byte);
+    }
+}
@@ -1,3 +1,4 @@
+use crate::core::Cursor;
 use crate::rpc::*;
 use serde_json::json;
 
@@ -27,12 +28,120 @@ fn from_analyze_params() {
 fn to_json_message() {
     let r = AnalyzeResult {
         grammar: GrammarMode::Code,
+        range: ContextRange {
+            grammar: GrammarMode::Code, start: Cursor::new(0, 0), end: Cursor::new(1, 2), start_byte: 0, end_byte: 5,
+            start_inclusive: true, end_inclusive: true,
+        },
+        ranges: None,
     };
-    let res = ClientResponse::new(0, true, None, Some(CommandResult::from_analyze_result(r)))
-        .to_json_message();
-    let res_json: serde_json::Value = serde_json::from_str(&res).unwrap();
+    let res = Encoding::Json.encode(&ClientResponse::new(0, true, None, Some(CommandResult::from_analyze_result(r))));
+    let res_json: serde_json::Value = serde_json::from_slice(&res).unwrap();
     let mes = json!({
-        "cid": 0, "success": true, "error": null, "result": { "grammar": "Code"}
+        "id": null, "cid": 0, "success": true, "error": null, "result": {
+            "grammar": "Code",
+            "range": {
+                "grammar": "Code", "start": { "row": 0, "column": 0 }, "end": { "row": 1, "column": 2 },
+                "start_byte": 0, "end_byte": 5, "start_inclusive": true, "end_inclusive": true,
+            },
+            "ranges": null,
+        }
     });
     assert_eq!(res_json, mes);
 }
+
+#[test]
+fn failure_json_message() {
+    let err = ResponseError::new(ErrorCode::UnsupportedLanguage, "Unsupported language: Brainfuck");
+    let res = Encoding::Json.encode(&ClientResponse::failure(3, err));
+    let res_json: serde_json::Value = serde_json::from_slice(&res).unwrap();
+    let mes = json!({
+        "id": null, "cid": 3, "success": false, "result": null,
+        "error": { "code": "UnsupportedLanguage", "message": "Unsupported language: Brainfuck", "data": null }
+    });
+    assert_eq!(res_json, mes);
+}
+
+#[test]
+fn method_changed_notification() {
+    let res = Encoding::Json.encode(&ServerNotification::method_changed(2, crate::core::InputMethodMode::Native));
+    let res_json: serde_json::Value = serde_json::from_slice(&res).unwrap();
+    let mes = json!({
+        "cid": 2, "notification": "MethodChanged", "result": { "method": "Native" }
+    });
+    assert_eq!(res_json, mes);
+}
+
+#[test]
+fn request_id_echo() {
+    let json_string = r#"{ "id": 17, "cid": 1, "command": "Subscribe", "params": {} }"#;
+    let req = Encoding::Json.decode::<ClientRequest>(json_string.as_bytes()).unwrap();
+    assert_eq!(req.id, Some(17));
+
+    let res = Encoding::Json.encode(&ClientResponse::new(1, true, None, None).with_id(req.id));
+    let res_json: serde_json::Value = serde_json::from_slice(&res).unwrap();
+    assert_eq!(res_json["id"], json!(17));
+
+    // 未提供 id 时兼容旧客户端
+    let json_string = r#"{ "cid": 1, "command": "Exit", "params": {} }"#;
+    assert_eq!(Encoding::Json.decode::<ClientRequest>(json_string.as_bytes()).unwrap().id, None);
+}
+
+#[test]
+fn from_cancel_params() {
+    let json_string = r#"{ "id": 9, "cid": 1, "command": "Cancel", "params": { "id": 7 } }"#;
+    let req = Encoding::Json.decode::<ClientRequest>(json_string.as_bytes()).unwrap();
+    assert!(matches!(req.command, CommandMode::Cancel));
+    assert_eq!(req.params.to_cancel_params().unwrap().id, 7);
+}
+
+#[test]
+fn auth_token_verify() {
+    let token = AuthToken::generate().unwrap();
+    assert_eq!(token.as_str().len(), 32);
+    assert!(token.verify(Some(token.as_str())));
+    assert!(!token.verify(None));
+    assert!(!token.verify(Some("")));
+    assert!(!token.verify(Some(&token.as_str()[1..])));
+    // 两次生成的令牌不同
+    assert_ne!(token.as_str(), AuthToken::generate().unwrap().as_str());
+
+    let path = std::env::temp_dir().join(format!("lazy-input-token-{}", std::process::id()));
+    std::fs::write(&path, "  secret\n").unwrap();
+    let token = AuthToken::from_file(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert!(token.verify(Some("secret")));
+}
+
+#[test]
+fn binary_encoding_roundtrip() {
+    let json_string = r#"
+            {
+                "id": 5,
+                "cid": 1,
+                "command": "Analyze",
+                "params": {
+                    "code": "let s = \"中文\";",
+                    "language": "Rust",
+                    "cursor": { "row": 0, "column": 5 }
+                }
+            }
+        "#;
+    let req: serde_json::Value = serde_json::from_str(json_string).unwrap();
+    for encoding in [Encoding::MessagePack, Encoding::Cbor] {
+        let decoded: ClientRequest = encoding.decode(&encoding.encode(&req)).unwrap();
+        assert_eq!(decoded.id, Some(5));
+        let analyze = decoded.params.to_analyze_params().unwrap();
+        assert_eq!(analyze.code, "let s = \"中文\";");
+        assert_eq!(analyze.cursor.column, 5);
+
+        let range = ContextRange {
+            grammar: GrammarMode::Code, start: Cursor::new(0, 0), end: Cursor::new(0, 5), start_byte: 0, end_byte: 5,
+            start_inclusive: true, end_inclusive: true,
+        };
+        let res = ClientResponse::success(1, CommandResult::from_analyze_result(AnalyzeResult { grammar: GrammarMode::Code, range, ranges: None }));
+        let res_value: serde_json::Value = encoding.decode(&encoding.encode(&res)).unwrap();
+        assert_eq!(res_value["result"]["grammar"], "Code");
+        assert_eq!(res_value["result"]["range"]["end"], json!({ "row": 0, "column": 5 }));
+    }
+    assert!(Encoding::MessagePack.decode::<ClientRequest>(b"{}").is_err());
+}
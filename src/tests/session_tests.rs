@@ -0,0 +1,72 @@
+use crate::core::{InputMethodMode, SupportLanguage};
+use crate::parser::Parser;
+use crate::server::*;
+use std::sync::Arc;
+
+#[test]
+fn new_session_on_zero_or_unknown_cid() {
+    let mut sessions = SessionManager::new();
+    let (first, resumed) = sessions.resolve(0);
+    assert!(!resumed);
+    assert_ne!(first, 0);
+
+    // 未知 cid 分配新会话, 而不是直接使用客户端提供的值
+    let (second, resumed) = sessions.resolve(4242);
+    assert!(!resumed);
+    assert_ne!(second, first);
+}
+
+#[test]
+fn resume_session_with_snapshot() {
+    let mut sessions = SessionManager::new();
+    let (cid, _) = sessions.resolve(0);
+    sessions.get_mut(cid).unwrap().method = Some(InputMethodMode::Native);
+
+    let (resumed_cid, resumed) = sessions.resolve(cid);
+    assert!(resumed);
+    assert_eq!(resumed_cid, cid);
+    assert_eq!(sessions.get_mut(cid).unwrap().method, Some(InputMethodMode::Native));
+}
+
+#[test]
+fn document_tree_cache() {
+    let lang = SupportLanguage::Rust;
+    let code = "// comment\nfn main() {}";
+    let mut parser = Parser::new(Arc::default());
+    parser.add_language(lang);
+    parser.build_tree(lang, code);
+
+    let mut sessions = SessionManager::new();
+    let (cid, _) = sessions.resolve(0);
+    let session = sessions.get_mut(cid).unwrap();
+    session.cache_tree("main.rs".to_string(), lang, code, parser.tree().unwrap().clone());
+
+    assert!(session.cached_tree("main.rs", lang, code).is_some());
+    // 代码变化 / 语言变化 / 文档不同 均不命中缓存
+    assert!(session.cached_tree("main.rs", lang, "fn main() {}").is_none());
+    assert!(session.cached_tree("main.rs", SupportLanguage::C, code).is_none());
+    assert!(session.cached_tree("lib.rs", lang, code).is_none());
+}
+
+#[test]
+fn bound_sessions_not_evicted() {
+    let mut sessions = SessionManager::new();
+    let cids: Vec<u16> = (0..32).map(|_| sessions.resolve(0).0).collect();
+    // 最久未活动的会话仍绑定着连接, 淘汰的是无连接绑定的会话
+    for &cid in &cids[1..] {
+        sessions.release(cid);
+    }
+    let (cid, _) = sessions.resolve(0);
+    assert!(!cids.contains(&cid));
+    assert_eq!(sessions.len(), 32);
+    assert!(sessions.get_mut(cids[0]).is_some());
+    assert_eq!(cids[1..].iter().filter(|&&cid| sessions.get_mut(cid).is_none()).count(), 1);
+
+    // 所有会话均绑定连接时不淘汰, 也不复用连接持有的 cid
+    let bound: Vec<u16> = (0..31).map(|_| sessions.resolve(0).0).chain([cids[0], cid]).collect();
+    assert_eq!(sessions.len(), 33);
+    let (cid, resumed) = sessions.resolve(0);
+    assert!(!resumed);
+    assert!(!bound.contains(&cid));
+    assert_eq!(sessions.len(), 34);
+}
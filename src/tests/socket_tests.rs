@@ -0,0 +1,108 @@
+use crate::config::Config;
+use crate::rpc::*;
+use std::io::Write;
+use std::net::{TcpListener, TcpStream};
+use std::time::Duration;
+
+const MAX_SIZE: usize = 1024;
+const TIMEOUT: Duration = Duration::from_millis(200);
+
+/// 建立一对本地连接 (客户端, 服务端)
+fn socket_pair() -> (TcpStream, TcpStream) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+    let (server, _) = listener.accept().unwrap();
+    (client, server)
+}
+
+fn frame(payload: &[u8]) -> Vec<u8> {
+    let mut buffer = (payload.len() as u64).to_be_bytes().to_vec();
+    buffer.extend_from_slice(payload);
+    buffer
+}
+
+#[test]
+fn valid_frame() {
+    let (mut client, mut server) = socket_pair();
+    client.write_all(&frame(b"{}")).unwrap();
+    assert_eq!(recv_message(&mut server, MAX_SIZE, None, TIMEOUT).unwrap(), b"{}");
+}
+
+#[test]
+fn frame_too_large() {
+    let (mut client, mut server) = socket_pair();
+    // 声明极大的长度, 服务端不应尝试分配内存
+    client.write_all(&u64::MAX.to_be_bytes()).unwrap();
+    match recv_message(&mut server, MAX_SIZE, None, TIMEOUT) {
+        Err(FrameError::TooLarge { size, limit }) => {
+            assert_eq!(size, u64::MAX);
+            assert_eq!(limit, MAX_SIZE);
+        },
+        other => panic!("Expect FrameTooLarge, got {other:?}"),
+    }
+}
+
+#[test]
+fn invalid_utf8_keeps_connection() {
+    let (mut client, mut server) = socket_pair();
+    client.write_all(&frame(&[0xff, 0xfe, 0xfd])).unwrap();
+    client.write_all(&frame(b"{\"ok\":1}")).unwrap();
+    // 消息帧完整读取, 解码失败不影响后续消息
+    let message = recv_message(&mut server, MAX_SIZE, None, TIMEOUT).unwrap();
+    assert!(Encoding::Json.decode::<serde_json::Value>(&message).is_err());
+    assert_eq!(recv_message(&mut server, MAX_SIZE, None, TIMEOUT).unwrap(), b"{\"ok\":1}");
+}
+
+#[test]
+fn truncated_frame_times_out() {
+    let (mut client, mut server) = socket_pair();
+    // 声明 10 字节但只发送 3 字节, 客户端保持连接不再发送
+    client.write_all(&10u64.to_be_bytes()).unwrap();
+    client.write_all(b"abc").unwrap();
+    match recv_message(&mut server, MAX_SIZE, None, TIMEOUT) {
+        Err(FrameError::Io(e)) => assert!(matches!(
+            e.kind(), std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut
+        )),
+        other => panic!("Expect timeout, got {other:?}"),
+    }
+}
+
+#[test]
+fn truncated_length_then_closed() {
+    let (mut client, mut server) = socket_pair();
+    client.write_all(&[0, 0, 0]).unwrap();
+    drop(client);
+    match recv_message(&mut server, MAX_SIZE, None, TIMEOUT) {
+        Err(FrameError::Io(e)) => assert_eq!(e.kind(), std::io::ErrorKind::UnexpectedEof),
+        other => panic!("Expect eof, got {other:?}"),
+    }
+}
+
+#[test]
+fn frame_config_from_args() {
+    let args = ["--max-frame-size", "2048", "--read-timeout", "3"].map(String::from);
+    let config = Config::from_args(args).unwrap();
+    assert_eq!(config.max_frame_size, 2048);
+    assert_eq!(config.read_timeout, Duration::from_secs(3));
+
+    assert!(Config::from_args(["--read-timeout", "0"].map(String::from)).is_err());
+    let config = Config::from_args(["--write-timeout", "5"].map(String::from)).unwrap();
+    assert_eq!(config.write_timeout, Duration::from_secs(5));
+    assert!(Config::from_args(["--write-timeout", "0"].map(String::from)).is_err());
+    let config = Config::from_args(["--auth-timeout", "2"].map(String::from)).unwrap();
+    assert_eq!(config.auth_timeout, Duration::from_secs(2));
+    assert!(Config::from_args(["--auth-timeout", "0"].map(String::from)).is_err());
+    assert!(Config::from_args(["--max-frame-size"].map(String::from)).is_err());
+}
+
+#[test]
+fn wait_for_message_timeout() {
+    let (mut client, mut server) = socket_pair();
+    // 未到达任何消息时 等待超时
+    match recv_message(&mut server, MAX_SIZE, Some(TIMEOUT), TIMEOUT) {
+        Err(FrameError::Io(e)) => assert!(matches!(e.kind(), std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut)),
+        other => panic!("unexpected result: {other:?}"),
+    }
+    client.write_all(&frame(b"{}")).unwrap();
+    assert_eq!(recv_message(&mut server, MAX_SIZE, Some(TIMEOUT), TIMEOUT).unwrap(), b"{}");
+}
@@ -0,0 +1,54 @@
+use crate::core::SupportLanguage;
+use crate::rpc::*;
+use crate::server::*;
+use std::time::Duration;
+
+#[test]
+fn latency_percentiles() {
+    let mut stats = Stats::new();
+    for ms in 1..=100 {
+        stats.record_latency(Metric::Parse, Duration::from_millis(ms));
+    }
+    let report = stats.report(0, 0);
+    let parse = report.latency.parse;
+    assert_eq!(parse.count, 100);
+    assert_eq!(parse.avg_us, 50_500);
+    assert_eq!(parse.p50_us, 50_000);
+    assert_eq!(parse.p95_us, 95_000);
+    assert_eq!(parse.p99_us, 99_000);
+    assert_eq!(parse.max_us, 100_000);
+    // 没有样本的统计项全部为 0
+    assert_eq!(report.latency.switch, LatencySummary::default());
+}
+
+#[test]
+fn status_report() {
+    let mut stats = Stats::new();
+    stats.record_request(CommandMode::Analyze);
+    stats.record_request(CommandMode::Analyze);
+    stats.record_request(CommandMode::Status);
+    stats.record_language(SupportLanguage::Rust);
+    stats.record_language(SupportLanguage::Rust);
+    stats.init_backend::<String>(Ok("fcitx5"));
+    stats.record_backend::<(), _>(&Err("Script failed"));
+
+    let report = stats.report(2, 3);
+    assert_eq!((report.clients, report.sessions), (2, 3));
+    assert_eq!(report.languages, vec!["rust".to_string()]);
+    assert_eq!(report.requests.get(&CommandMode::Analyze), Some(&2));
+    assert_eq!(report.requests.get(&CommandMode::Switch), None);
+    assert_eq!(report.backend.name.as_deref(), Some("fcitx5"));
+    assert!(!report.backend.healthy);
+    assert_eq!(report.backend.last_error.unwrap().message, "Script failed");
+
+    // 后端调用恢复成功后 仍保留最近一次错误
+    let mut stats = Stats::new();
+    stats.init_backend::<String>(Ok("fcitx5"));
+    stats.record_backend::<(), _>(&Err("Script failed"));
+    stats.record_backend::<(), &str>(&Ok(()));
+    let report = stats.report(0, 0);
+    assert!(report.backend.healthy);
+    assert!(report.backend.last_error.is_some());
+    let json = serde_json::to_value(&report).unwrap();
+    assert!(json["requests"].is_object());
+}
@@ -0,0 +1,77 @@
+use super::*;
+use crate::replay::replay;
+use crate::server::*;
+use serde_json::{json, Value};
+use std::time::Duration;
+
+/// 使用模拟输入法后端运行服务端并记录 trace, 依次发送请求后退出
+fn record_trace(path: &std::path::Path, redact: bool, requests: &[Value]) {
+    let mut args = vec!["--mock-switcher", "--idle-timeout", "never", "--trace", path.to_str().unwrap()];
+    if redact {
+        args.push("--trace-redact");
+    };
+    let (port, token, stopped) = spawn_server(&args);
+
+    let mut client = connect(port);
+    for (i, request) in requests.iter().enumerate() {
+        let mut request = request.clone();
+        if i == 0 {
+            request["token"] = json!(token.as_str());
+        };
+        send(&mut client, request.clone());
+        if request["command"] != "Exit" {
+            recv(&mut client);
+        };
+    }
+    assert_eq!(stopped.recv_timeout(Duration::from_secs(3)), Ok(StopReason::Exit));
+}
+
+fn requests() -> Vec<Value> {
+    let cursor = json!({ "row": 0, "column": 15 });
+    vec![
+        json!({ "cid": 0, "command": "Analyze", "params": { "document": "/home/me/secret.rs", "code": "fn main() { // hi\n}", "language": "Rust", "cursor": cursor } }),
+        json!({ "cid": 1, "id": 7, "command": "Switch", "params": { "code": "fn main() {}", "language": "Rust", "cursor": cursor } }),
+        json!({ "cid": 1, "command": "MethodOnly", "params": { "mode": "Native" } }),
+        json!({ "cid": 1, "command": "Analyze", "params": { "code": "", "language": "Nope", "cursor": cursor } }),
+        json!({ "cid": 1, "command": "Exit", "params": {} }),
+    ]
+}
+
+#[test]
+fn trace_replay_without_diffs() {
+    let path = std::env::temp_dir().join(format!("lazy-input-switcher-trace-{}.jsonl", std::process::id()));
+    record_trace(&path, false, &requests());
+    let content = std::fs::read_to_string(&path).unwrap();
+    // 令牌不会被记录
+    assert!(content.lines().all(|line| !line.contains("\"token\"")));
+
+    let report = replay(&path).unwrap();
+    assert_eq!((report.replayed, report.skipped, report.diffs.len()), (4, 1, 0));
+
+    // 篡改记录的响应后 回放报告差异
+    std::fs::write(&path, content.replace("\"method\":\"Native\"", "\"method\":\"English\"")).unwrap();
+    let report = replay(&path).unwrap();
+    assert_eq!(report.diffs.len(), 1);
+    assert_eq!(report.diffs[0].command, "MethodOnly");
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn trace_redacts_code() {
+    let path = std::env::temp_dir().join(format!("lazy-input-switcher-redact-{}.jsonl", std::process::id()));
+    record_trace(&path, true, &requests());
+    let content = std::fs::read_to_string(&path).unwrap();
+    assert!(!content.contains("fn main()"));
+    assert!(content.contains("<redacted 19 bytes>"));
+    assert!(!content.contains("secret.rs"));
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        assert_eq!(std::fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+    }
+
+    // 脱敏的请求不会被回放
+    let report = replay(&path).unwrap();
+    assert_eq!((report.replayed, report.skipped, report.diffs.len()), (1, 4, 0));
+    std::fs::remove_file(&path).unwrap();
+}
@@ -0,0 +1,90 @@
+use super::*;
+use crate::config::Config;
+use crate::core::InputMethodMode;
+use crate::switch::MockGate;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::net::TcpStream;
+use std::sync::mpsc::{Receiver, Sender};
+use std::time::Duration;
+
+const CODE: &str = "fn main() { // hi\n}";
+
+/// 使用带闸门的模拟输入法后端运行服务端, 返回已通过认证的连接、分配的 cid 与 闸门的通知、放行通道
+fn authenticate() -> (TcpStream, Value, Receiver<InputMethodMode>, Sender<()>) {
+    let (gate, entered, release) = MockGate::new();
+    let (port, token, _) = spawn_server_with(Config { mock_switcher: true, mock_gate: Some(gate), ..Config::default() });
+    let mut client = connect(port);
+    let params = json!({ "code": CODE, "language": "Rust", "cursor": { "row": 0, "column": 3 } });
+    let response = request(&mut client, json!({ "token": token.as_str(), "id": 0, "cid": 0, "command": "Analyze", "params": params }));
+    (client, response["cid"].clone(), entered, release)
+}
+
+/// 接收 count 条响应, 按请求 id 索引
+fn recv_all(client: &mut TcpStream, count: usize) -> HashMap<u64, Value> {
+    (0..count).map(|_| {
+        let response = recv(client);
+        (response["id"].as_u64().unwrap(), response)
+    }).collect()
+}
+
+fn switch(cid: &Value, id: u64, document: Option<&str>, column: usize) -> Value {
+    let params = json!({ "document": document, "code": CODE, "language": "Rust", "cursor": { "row": 0, "column": column } });
+    json!({ "cid": cid, "id": id, "command": "Switch", "params": params })
+}
+
+/// 发送 Switch 请求 (English -> Native) 并等待其阻塞在闸门中, 之后的请求将在队列中等待
+fn occupy(client: &mut TcpStream, cid: &Value, entered: &Receiver<InputMethodMode>) {
+    send(client, switch(cid, 1, None, 15));
+    assert_eq!(entered.recv_timeout(Duration::from_secs(3)), Ok(InputMethodMode::Native));
+}
+
+/// 连接线程按顺序处理请求并直接回答 Analyze, 收到其响应时 之前的请求均已进入工作线程队列, 随后放行切换
+fn release_queued(client: &mut TcpStream, cid: &Value, release: Sender<()>) {
+    let params = json!({ "code": CODE, "language": "Rust", "cursor": { "row": 0, "column": 3 } });
+    let response = request(client, json!({ "cid": cid, "id": 0, "command": "Analyze", "params": params }));
+    assert_eq!(response["id"], 0);
+    drop(release);
+}
+
+#[test]
+fn cancel_queued_switch() {
+    let (mut client, cid, entered, release) = authenticate();
+    occupy(&mut client, &cid, &entered);
+    send(&mut client, switch(&cid, 2, Some("main.rs"), 3));
+    send(&mut client, json!({ "cid": cid, "id": 3, "command": "Cancel", "params": { "id": 2 } }));
+    // 正在执行的请求 与 未知请求均无法取消
+    send(&mut client, json!({ "cid": cid, "id": 4, "command": "Cancel", "params": { "id": 1 } }));
+    send(&mut client, json!({ "cid": cid, "id": 5, "command": "Cancel", "params": { "id": 99 } }));
+    release_queued(&mut client, &cid, release);
+
+    let responses = recv_all(&mut client, 5);
+    assert_eq!(responses[&1]["result"]["method"], "Native");
+    assert_eq!(responses[&2]["success"], false);
+    assert_eq!(responses[&2]["error"]["code"], "Cancelled");
+    assert_eq!(responses[&3]["result"]["cancelled"], true);
+    assert_eq!(responses[&4]["result"]["cancelled"], false);
+    assert_eq!(responses[&5]["result"]["cancelled"], false);
+}
+
+#[test]
+fn newer_switch_supersedes_queued() {
+    let (mut client, cid, entered, release) = authenticate();
+    occupy(&mut client, &cid, &entered);
+    send(&mut client, switch(&cid, 2, Some("main.rs"), 3));
+    send(&mut client, switch(&cid, 3, Some("main.rs"), 15));
+    // 其他文档 与 未提供文档标识的请求不受影响
+    send(&mut client, switch(&cid, 4, Some("lib.rs"), 15));
+    send(&mut client, switch(&cid, 5, None, 3));
+    send(&mut client, switch(&cid, 6, None, 15));
+    release_queued(&mut client, &cid, release);
+
+    let responses = recv_all(&mut client, 6);
+    assert_eq!(responses[&2]["success"], false);
+    assert_eq!(responses[&2]["error"]["code"], "Cancelled");
+    for id in [1, 3, 4, 5, 6] {
+        assert_eq!(responses[&id]["success"], true, "request {id}");
+    }
+    assert_eq!(responses[&5]["result"]["method"], "English");
+    assert_eq!(responses[&6]["result"]["method"], "Native");
+}